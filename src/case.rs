@@ -0,0 +1,144 @@
+use anyhow::{anyhow, Error, Result};
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Case {
+    Kebab,
+    Camel,
+    Pascal,
+    Snake,
+}
+
+pub const CASES: [&str; 4] = ["kebab", "camel", "pascal", "snake"];
+
+impl FromStr for Case {
+    type Err = Error;
+
+    fn from_str(name: &str) -> Result<Self> {
+        match name.to_lowercase().as_str() {
+            "kebab" => Ok(Case::Kebab),
+            "camel" => Ok(Case::Camel),
+            "pascal" => Ok(Case::Pascal),
+            "snake" => Ok(Case::Snake),
+            _ => Err(anyhow!(
+                "Unknown case {:?}, expected one of {:?}",
+                name,
+                CASES
+            )),
+        }
+    }
+}
+
+// Splits on separators and case changes. A run of capitals followed by a
+// lower case letter ends before the last capital, so HTMLParser is html and
+// parser. Digits stick to the preceding word.
+fn to_words(name: &str) -> Vec<String> {
+    let chars: Vec<char> = name.chars().collect();
+    let mut words: Vec<String> = vec![];
+    let mut word = String::new();
+
+    for (idx, &c) in chars.iter().enumerate() {
+        if c == '-' || c == '_' || c == ' ' {
+            if !word.is_empty() {
+                words.push(word);
+                word = String::new();
+            }
+            continue;
+        }
+
+        let prev = idx.checked_sub(1).map(|prev| chars[prev]);
+        let next = chars.get(idx + 1);
+
+        let is_boundary = c.is_uppercase()
+            && match prev {
+                Some(prev) if prev.is_lowercase() || prev.is_numeric() => true,
+                Some(prev) if prev.is_uppercase() => next.is_some_and(|next| next.is_lowercase()),
+                _ => false,
+            };
+
+        if is_boundary && !word.is_empty() {
+            words.push(word);
+            word = String::new();
+        }
+        word.push(c);
+    }
+
+    if !word.is_empty() {
+        words.push(word);
+    }
+
+    words
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first
+            .to_uppercase()
+            .chain(chars.flat_map(char::to_lowercase))
+            .collect(),
+        None => String::new(),
+    }
+}
+
+impl Case {
+    pub fn convert(&self, name: &str) -> String {
+        let words = to_words(name);
+
+        match self {
+            Case::Kebab => words.join("-").to_lowercase(),
+            Case::Snake => words.join("_").to_lowercase(),
+            Case::Pascal => words.iter().map(|word| capitalize(word)).collect(),
+            Case::Camel => words
+                .iter()
+                .enumerate()
+                .map(|(idx, word)| match idx {
+                    0 => word.to_lowercase(),
+                    _ => capitalize(word),
+                })
+                .collect(),
+        }
+    }
+
+    // Converts the part of a file name before the first dot, so that suffixes
+    // such as .test.tsx or .d.ts are kept as is.
+    pub fn convert_file_name(&self, file_name: &str) -> String {
+        match file_name.find('.') {
+            Some(0) => file_name.to_owned(),
+            Some(idx) => self.convert(&file_name[..idx]) + &file_name[idx..],
+            None => self.convert(file_name),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Case;
+
+    macro_rules! convert_tests {
+        ($($name:ident: $value:expr,)*) => {
+        $(
+            #[test]
+            fn $name() {
+                let (case, file_name, expected) = $value;
+                let result = case.convert_file_name(file_name);
+                assert_eq!(result, expected);
+            }
+        )*
+        }
+    }
+
+    convert_tests! {
+        kebab_0: (Case::Kebab, "UserView.tsx", "user-view.tsx"),
+        kebab_1: (Case::Kebab, "HTMLParser.test.ts", "html-parser.test.ts"),
+        kebab_2: (Case::Kebab, "user_view2Api.d.ts", "user-view2-api.d.ts"),
+        kebab_3: (Case::Kebab, "Button.tsx", "button.tsx"),
+        kebab_4: (Case::Kebab, "index.ts", "index.ts"),
+        camel_0: (Case::Camel, "user-view.tsx", "userView.tsx"),
+        camel_1: (Case::Camel, "HTMLParser.ts", "htmlParser.ts"),
+        pascal_0: (Case::Pascal, "user_view.tsx", "UserView.tsx"),
+        pascal_1: (Case::Pascal, "userView.stories.tsx", "UserView.stories.tsx"),
+        snake_0: (Case::Snake, "UserView.tsx", "user_view.tsx"),
+        snake_1: (Case::Snake, ".eslintrc.ts", ".eslintrc.ts"),
+    }
+}
@@ -0,0 +1,134 @@
+use anyhow::{anyhow, Result};
+use similar::TextDiff;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::edit;
+use crate::path;
+use crate::plan::{self, FileEdit, MovePlan, Rename};
+
+fn to_diff_path(base: &Path, file: &Path) -> Result<String> {
+    let rel_path = path::diff(base, file)?;
+    rel_path
+        .to_str()
+        .map(|rel_path| rel_path.replace('\\', "/"))
+        .ok_or_else(|| anyhow!("Non utf-8 path"))
+}
+
+fn hunks(FileEdit { path, edits }: &FileEdit) -> Result<String> {
+    let source_code = fs::read_to_string(path).map_err(|_| anyhow!("Failed to read {:?}", path))?;
+    let new_source_code = edit::apply_edits(&source_code, edits)?;
+
+    Ok(TextDiff::from_lines(&source_code, &new_source_code)
+        .unified_diff()
+        .context_radius(3)
+        .to_string())
+}
+
+// Folder renames are listed file by file, as git does.
+fn file_renames(renames: &[Rename]) -> Result<Vec<(PathBuf, PathBuf)>> {
+    let mut file_renames = plan::to_moved_files(renames)?;
+    file_renames.sort();
+    Ok(file_renames)
+}
+
+// Renders changes as a git style patch with paths relative to base, so that
+// it can be applied with `git apply` from base.
+pub fn unified_diff(base: &Path, MovePlan { renames, edits }: &MovePlan) -> Result<String> {
+    let mut edits: HashMap<&PathBuf, &FileEdit> =
+        edits.iter().map(|edit| (&edit.path, edit)).collect();
+    let mut output = String::new();
+
+    for (source_file, target_file) in file_renames(renames)? {
+        let from = to_diff_path(base, &source_file)?;
+        let to = to_diff_path(base, &target_file)?;
+
+        output += &format!("diff --git a/{} b/{}\n", from, to);
+        output += &format!("rename from {}\nrename to {}\n", from, to);
+
+        if let Some(edit) = edits.remove(&source_file) {
+            output += &format!("--- a/{}\n+++ b/{}\n", from, to);
+            output += &hunks(edit)?;
+        }
+    }
+
+    let mut edits: Vec<&FileEdit> = edits.into_values().collect();
+    edits.sort_by(|a, b| a.path.cmp(&b.path));
+
+    for edit in edits {
+        let file = to_diff_path(base, &edit.path)?;
+
+        output += &format!("diff --git a/{} b/{}\n", file, file);
+        output += &format!("--- a/{}\n+++ b/{}\n", file, file);
+        output += &hunks(edit)?;
+    }
+
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use std::fs;
+
+    use crate::edit::TextEdit;
+    use crate::plan::{FileEdit, MovePlan, Rename};
+    use crate::test_dir::TempDir;
+
+    #[test]
+    fn it_renders_renames_and_edits() -> Result<()> {
+        let base = TempDir::new("diff");
+        fs::create_dir_all(base.join("src"))?;
+        fs::write(base.join("src/main.ts"), "import a from './a';\n")?;
+        fs::write(base.join("src/a.ts"), "import x from './x';\n")?;
+
+        let plan = MovePlan {
+            renames: vec![Rename {
+                from: base.join("src/a.ts"),
+                to: base.join("src/b/a.ts"),
+            }],
+            edits: vec![
+                FileEdit {
+                    path: base.join("src/main.ts"),
+                    edits: vec![TextEdit {
+                        start: 15,
+                        end: 18,
+                        old_text: "./a".into(),
+                        new_text: "./b/a".into(),
+                    }],
+                },
+                FileEdit {
+                    path: base.join("src/a.ts"),
+                    edits: vec![TextEdit {
+                        start: 15,
+                        end: 18,
+                        old_text: "./x".into(),
+                        new_text: "../x".into(),
+                    }],
+                },
+            ],
+        };
+
+        let result = super::unified_diff(&base, &plan)?;
+
+        let expected = "\
+diff --git a/src/a.ts b/src/b/a.ts
+rename from src/a.ts
+rename to src/b/a.ts
+--- a/src/a.ts
++++ b/src/b/a.ts
+@@ -1 +1 @@
+-import x from './x';
++import x from '../x';
+diff --git a/src/main.ts b/src/main.ts
+--- a/src/main.ts
++++ b/src/main.ts
+@@ -1 +1 @@
+-import a from './a';
++import a from './b/a';
+";
+        assert_eq!(result, expected);
+        Ok(())
+    }
+}
@@ -1,67 +1,75 @@
 use anyhow::{anyhow, Result};
-use ropey::Rope;
-use std::path::PathBuf;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
 
 use crate::import_string;
 use crate::parser::{ImportFinder, Lang};
+use crate::resolver::Resolver;
 
-fn infer_langauge_from_suffix(file_name: &PathBuf) -> Result<Lang> {
+fn infer_langauge_from_suffix(file_name: &Path) -> Result<Lang> {
     let suffix = file_name
         .extension()
         .and_then(|os_str| os_str.to_str())
         .ok_or_else(|| anyhow!("Missing suffix on file"))?;
 
+    // The JavaScript grammar parses JSX as well.
     match suffix {
-        "ts" => Ok(Lang::TypeScript),
+        "ts" | "mts" | "cts" => Ok(Lang::TypeScript),
         "tsx" => Ok(Lang::TypeScriptTsx),
+        "js" | "jsx" | "mjs" | "cjs" => Ok(Lang::JavaScript),
         suffix => Err(anyhow!("{:?} files are not supported", suffix)),
     }
 }
 
-fn replace_rel_imports<F>(source_code: &str, lang: Lang, replacer: F) -> Result<String>
-where
-    F: Fn(&String) -> Result<String>,
-{
-    let mut import_finder = ImportFinder::new(&source_code, lang)?;
-    let mut rope = Rope::from_str(&source_code);
-
-    for text_slice in import_finder.find_imports() {
-        let (start_idx, end_idx) = text_slice.to_index_range(&rope);
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TextEdit {
+    pub start: usize,
+    pub end: usize,
+    pub old_text: String,
+    pub new_text: String,
+}
 
-        let old_import = rope.slice(start_idx..end_idx).to_string();
+pub fn apply_edits(source_code: &str, edits: &[TextEdit]) -> Result<String> {
+    let mut edits: Vec<&TextEdit> = edits.iter().collect();
+    edits.sort_by_key(|edit| edit.start);
 
-        if !old_import.starts_with('.') {
-            continue;
-        }
+    let mut new_source_code = String::with_capacity(source_code.len());
+    let mut idx = 0;
 
-        let new_import = replacer(&old_import)?;
+    for edit in edits {
+        let old_text = source_code
+            .get(edit.start..edit.end)
+            .filter(|_| edit.start >= idx)
+            .ok_or_else(|| anyhow!("Edit {}..{} is out of range", edit.start, edit.end))?;
 
-        if old_import.eq(&new_import) {
-            continue;
+        if !old_text.eq(&edit.old_text) {
+            return Err(anyhow!(
+                "Expected {:?} at {}..{} but found {:?}",
+                edit.old_text,
+                edit.start,
+                edit.end,
+                old_text
+            ));
         }
 
-        rope.remove(start_idx..end_idx);
-        rope.insert(start_idx, &new_import);
+        new_source_code += &source_code[idx..edit.start];
+        new_source_code += &edit.new_text;
+        idx = edit.end;
     }
-    Ok(rope.to_string())
+
+    new_source_code += &source_code[idx..];
+    Ok(new_source_code)
 }
 
-pub fn replace_imports<F>(source_file: &PathBuf, source_code: &str, replacer: F) -> Result<String>
+fn find_import_edits<F>(source_code: &str, lang: Lang, replacer: F) -> Result<Vec<TextEdit>>
 where
     F: Fn(&String) -> Result<String>,
 {
-    let lang = infer_langauge_from_suffix(&source_file)?;
-    let mut import_finder = ImportFinder::new(&source_code, lang)?;
-    let mut rope = Rope::from_str(&source_code);
+    let mut import_finder = ImportFinder::new(source_code, lang)?;
+    let mut edits = vec![];
 
     for text_slice in import_finder.find_imports() {
-        let (start_idx, end_idx) = text_slice.to_index_range(&rope);
-
-        let old_import = rope.slice(start_idx..end_idx).to_string();
-
-        if !old_import.starts_with('.') {
-            continue;
-        }
+        let old_import = source_code[text_slice.start..text_slice.end].to_string();
 
         let new_import = replacer(&old_import)?;
 
@@ -69,50 +77,125 @@ where
             continue;
         }
 
-        rope.remove(start_idx..end_idx);
-        rope.insert(start_idx, &new_import);
+        edits.push(TextEdit {
+            start: text_slice.start,
+            end: text_slice.end,
+            old_text: old_import,
+            new_text: new_import,
+        });
     }
-    Ok(rope.to_string())
+    Ok(edits)
+}
+
+// Passes every import string, aliased and package imports included, to the
+// replacer and collects the ones it changes as edits.
+pub fn replace_imports<F>(
+    source_file: &Path,
+    source_code: &str,
+    replacer: F,
+) -> Result<Vec<TextEdit>>
+where
+    F: Fn(&String) -> Result<String>,
+{
+    let lang = infer_langauge_from_suffix(source_file)?;
+    find_import_edits(source_code, lang, replacer)
 }
 
+// Rewrites the imports of a moved file, including imports of files that are
+// moved along with it.
 pub fn move_source_file(
-    source_code: String,
-    source_file: &PathBuf,
-    target_file: &PathBuf,
-) -> Result<String> {
-    let lang = infer_langauge_from_suffix(&source_file)?;
-    replace_rel_imports(&source_code, lang, |import_string| {
+    source_code: &str,
+    source_file: &Path,
+    target_file: &Path,
+    moved_files: &[(PathBuf, PathBuf)],
+    resolver: &Resolver,
+) -> Result<Vec<TextEdit>> {
+    replace_imports(source_file, source_code, |import_string| {
+        let has_moved = moved_files.iter().find(|(moved_file, _)| {
+            resolver
+                .is_import_from(source_file, moved_file, import_string)
+                .unwrap_or(false)
+        });
+
+        if import_string.starts_with('#') {
+            return match has_moved {
+                Some((old_location, new_location)) => {
+                    let args = import_string::RequiredFileRename {
+                        source_file,
+                        import_string,
+                        old_location,
+                        new_location,
+                    };
+                    resolver.rename_subpath_import(&args, target_file)
+                }
+                None => {
+                    resolver.move_subpath_import(source_file, target_file, import_string.clone())
+                }
+            };
+        }
+
+        let import_string = match has_moved {
+            Some((old_location, new_location)) => {
+                let args = import_string::RequiredFileRename {
+                    source_file,
+                    import_string,
+                    old_location,
+                    new_location,
+                };
+                resolver.rename_required_file(&args)?
+            }
+            None => import_string.clone(),
+        };
+
+        if !import_string.starts_with('.') {
+            return resolver.to_relative_import(source_file, target_file, import_string);
+        }
+
         let args = import_string::SourceFileRename {
-            import_string,
+            import_string: &import_string,
             old_location: source_file,
             new_location: target_file,
         };
-        import_string::rename_source_file(&args)
+        let new_import_string = import_string::rename_source_file(&args)?;
+        resolver.to_package_import(target_file, new_import_string)
     })
 }
 
+// Rewrites imports of moved files in a file that stays in place.
 pub fn move_required_file(
     source_code: &str,
-    source_file: &PathBuf,
-    old_import_location: &PathBuf,
-    new_import_location: &PathBuf,
-) -> Result<String> {
-    let lang = infer_langauge_from_suffix(&source_file)?;
-    replace_rel_imports(&source_code, lang, |import_string| {
-        let args = import_string::RequiredFileRename {
-            source_file,
-            import_string,
-            old_location: old_import_location,
-            new_location: new_import_location,
-        };
-        import_string::rename_required_file(&args)
+    source_file: &Path,
+    moved_files: &[(PathBuf, PathBuf)],
+    resolver: &Resolver,
+) -> Result<Vec<TextEdit>> {
+    replace_imports(source_file, source_code, |import_string| {
+        let has_moved = moved_files.iter().find(|(moved_file, _)| {
+            resolver
+                .is_import_from(source_file, moved_file, import_string)
+                .unwrap_or(false)
+        });
+
+        match has_moved {
+            Some((old_location, new_location)) => {
+                let args = import_string::RequiredFileRename {
+                    source_file,
+                    import_string,
+                    old_location,
+                    new_location,
+                };
+                resolver.rename_required_file(&args)
+            }
+            None => Ok(import_string.clone()),
+        }
     })
 }
 
 #[cfg(test)]
 mod tests {
     use anyhow::Result;
-    use std::path::PathBuf;
+    use std::path::{Path, PathBuf};
+
+    use crate::resolver::Resolver;
 
     #[test]
     fn it_updates_imports_0() -> Result<()> {
@@ -128,7 +211,8 @@ mod tests {
         let source: PathBuf = "/src/a/b/c/d/source.ts".into();
         let target: PathBuf = "/src/a/b/c/d/e/target.ts".into();
 
-        let new_source_code = super::move_source_file(code, &source, &target)?;
+        let edits = super::move_source_file(&code, &source, &target, &[], &Resolver::default())?;
+        let new_source_code = super::apply_edits(&code, &edits)?;
 
         let new_import_0: String = "import some from '../../../some';".into();
         let new_import_1: String = "import other from '../../../other';".into();
@@ -152,7 +236,8 @@ mod tests {
         let source: PathBuf = "/src/a/b/c/d/source.ts".into();
         let target: PathBuf = "/src/a/target.ts".into();
 
-        let new_source_code = super::move_source_file(code, &source, &target)?;
+        let edits = super::move_source_file(&code, &source, &target, &[], &Resolver::default())?;
+        let new_source_code = super::apply_edits(&code, &edits)?;
 
         let new_import_0: String = "import some from './b/some';".into();
         let new_import_1: String = "import other from './b/other';".into();
@@ -161,4 +246,199 @@ mod tests {
         assert!(new_source_code.contains(&new_import_1));
         Ok(())
     }
+
+    #[test]
+    fn it_updates_require_calls() -> Result<()> {
+        let code: String = r#"
+            const some = require('../../some');
+            const { other } = require("../../other");
+            const fs = require('fs');
+            "#
+        .into();
+
+        let source: PathBuf = "/src/a/b/c/d/source.ts".into();
+        let target: PathBuf = "/src/a/b/c/d/e/target.ts".into();
+
+        let edits = super::move_source_file(&code, &source, &target, &[], &Resolver::default())?;
+        let new_source_code = super::apply_edits(&code, &edits)?;
+
+        assert!(new_source_code.contains("const some = require('../../../some');"));
+        assert!(new_source_code.contains("const { other } = require(\"../../../other\");"));
+        assert!(new_source_code.contains("const fs = require('fs');"));
+        Ok(())
+    }
+
+    #[test]
+    fn it_updates_required_file_in_require_calls() -> Result<()> {
+        let code = r#"
+            const some = require('./some');
+            const other = notRequire('./some');
+            "#;
+
+        let source: PathBuf = "/src/a/source.ts".into();
+        let old_location: PathBuf = "/src/a/some.ts".into();
+        let new_location: PathBuf = "/src/b/some.ts".into();
+
+        let moved_files = [(old_location, new_location)];
+        let edits = super::move_required_file(code, &source, &moved_files, &Resolver::default())?;
+        let new_source_code = super::apply_edits(code, &edits)?;
+
+        assert!(new_source_code.contains("const some = require('../b/some');"));
+        assert!(new_source_code.contains("const other = notRequire('./some');"));
+        Ok(())
+    }
+
+    #[test]
+    fn it_updates_dynamic_imports() -> Result<()> {
+        let code: String = r#"
+            const Foo = lazy(() => import('../pages/Foo'));
+            const routes = [{ loadChildren: () => import("../x").then((m) => m.XModule) }];
+            const dynamic = import(`../pages/${name}`);
+            "#
+        .into();
+
+        let source: PathBuf = "/src/app/routes.tsx".into();
+        let target: PathBuf = "/src/app/router/routes.tsx".into();
+
+        let edits = super::move_source_file(&code, &source, &target, &[], &Resolver::default())?;
+        let new_source_code = super::apply_edits(&code, &edits)?;
+
+        assert!(new_source_code.contains("lazy(() => import('../../pages/Foo'))"));
+        assert!(new_source_code.contains("loadChildren: () => import(\"../../x\")"));
+        assert!(new_source_code.contains("import(`../pages/${name}`)"));
+        Ok(())
+    }
+
+    #[test]
+    fn it_updates_import_require_clauses() -> Result<()> {
+        let code: String = r#"
+            import fs = require('../fs-helpers');
+            import type Types = require('../types');
+            export import util = require('../util');
+            export = fs;
+            "#
+        .into();
+
+        let source: PathBuf = "/src/namespaces/legacy.ts".into();
+        let target: PathBuf = "/src/namespaces/old/legacy.ts".into();
+
+        let edits = super::move_source_file(&code, &source, &target, &[], &Resolver::default())?;
+        let new_source_code = super::apply_edits(&code, &edits)?;
+
+        assert!(new_source_code.contains("import fs = require('../../fs-helpers');"));
+        assert!(new_source_code.contains("import type Types = require('../../types');"));
+        assert!(new_source_code.contains("export import util = require('../../util');"));
+        assert!(new_source_code.contains("export = fs;"));
+        Ok(())
+    }
+
+    #[test]
+    fn it_updates_export_assignment_of_require() -> Result<()> {
+        let code = "export = require('./helpers');";
+
+        let source: PathBuf = "/src/a/source.ts".into();
+        let old_location: PathBuf = "/src/a/helpers.ts".into();
+        let new_location: PathBuf = "/src/b/helpers.ts".into();
+
+        let moved_files = [(old_location, new_location)];
+        let edits = super::move_required_file(code, &source, &moved_files, &Resolver::default())?;
+        let new_source_code = super::apply_edits(code, &edits)?;
+
+        assert_eq!(new_source_code, "export = require('../b/helpers');");
+        Ok(())
+    }
+
+    #[test]
+    fn it_updates_tsconfig_path_aliases() -> Result<()> {
+        let resolver = Resolver::parse(
+            Path::new("/project"),
+            r#"{ "compilerOptions": { "paths": { "@app/*": ["src/*"] } } }"#,
+        )?;
+        let code = r#"
+            import { Button } from '@app/components/Button';
+            import { Other } from '@app/components/Other';
+            import React from 'react';
+            "#;
+
+        let source: PathBuf = "/project/src/views/Main.tsx".into();
+        let old_location: PathBuf = "/project/src/components/Button.tsx".into();
+        let new_location: PathBuf = "/project/src/ui/button/index.tsx".into();
+
+        let moved_files = [(old_location, new_location)];
+        let edits = super::move_required_file(code, &source, &moved_files, &resolver)?;
+        let new_source_code = super::apply_edits(code, &edits)?;
+
+        assert!(new_source_code.contains("import { Button } from '@app/ui/button';"));
+        assert!(new_source_code.contains("import { Other } from '@app/components/Other';"));
+        assert!(new_source_code.contains("import React from 'react';"));
+        Ok(())
+    }
+
+    #[test]
+    fn it_updates_imports_between_moved_files() -> Result<()> {
+        let code = r#"
+            import { b } from './b';
+            import { c } from '../c';
+            "#;
+
+        let source: PathBuf = "/src/a/a.ts".into();
+        let target: PathBuf = "/src/lib/a.ts".into();
+        let moved_files = [
+            (source.clone(), target.clone()),
+            ("/src/a/b.ts".into(), "/src/lib/shared/b.ts".into()),
+        ];
+
+        let edits =
+            super::move_source_file(code, &source, &target, &moved_files, &Resolver::default())?;
+        let new_source_code = super::apply_edits(code, &edits)?;
+
+        assert!(new_source_code.contains("import { b } from './shared/b';"));
+        assert!(new_source_code.contains("import { c } from '../c';"));
+        Ok(())
+    }
+
+    #[test]
+    fn it_updates_javascript_imports() -> Result<()> {
+        let code = r#"
+            import { a } from './a';
+            export * from './b';
+            const c = require('./c');
+            const Page = lazy(() => import('./Page'));
+            const App = () => <Page title="./a" />;
+            "#;
+
+        let source: PathBuf = "/src/app/App.jsx".into();
+        let target: PathBuf = "/src/App.jsx".into();
+
+        let edits = super::move_source_file(code, &source, &target, &[], &Resolver::default())?;
+        let new_source_code = super::apply_edits(code, &edits)?;
+
+        assert!(new_source_code.contains("import { a } from './app/a';"));
+        assert!(new_source_code.contains("export * from './app/b';"));
+        assert!(new_source_code.contains("const c = require('./app/c');"));
+        assert!(new_source_code.contains("lazy(() => import('./app/Page'))"));
+        assert!(new_source_code.contains("<Page title=\"./a\" />"));
+        Ok(())
+    }
+
+    #[test]
+    fn it_updates_imports_across_module_formats() -> Result<()> {
+        let code = r#"
+            import { util } from './util.mjs';
+            const helpers = require('./helpers');
+            "#;
+
+        let source: PathBuf = "/src/index.cjs".into();
+        let moved_files = [
+            ("/src/util.mjs".into(), "/src/lib/util.mjs".into()),
+            ("/src/helpers.ts".into(), "/src/lib/helpers.ts".into()),
+        ];
+
+        let edits = super::move_required_file(code, &source, &moved_files, &Resolver::default())?;
+        let new_source_code = super::apply_edits(code, &edits)?;
+
+        assert!(new_source_code.contains("import { util } from './lib/util.mjs';"));
+        assert!(new_source_code.contains("const helpers = require('./lib/helpers');"));
+        Ok(())
+    }
 }
@@ -1,16 +1,119 @@
-use ignore::Walk;
-use std::path::PathBuf;
+use anyhow::{anyhow, Result};
+use globset::GlobBuilder;
+use ignore::{Walk, WalkBuilder};
+use std::path::{Component, Path, PathBuf};
 
-pub fn iter_files(dir: &PathBuf) -> impl Iterator<Item = PathBuf> {
-    Walk::new(dir)
-        .into_iter()
+pub const GLOB_CHARS: [char; 4] = ['*', '?', '[', '{'];
+
+const SOURCE_SUFFIXES: [&str; 8] = ["ts", "tsx", "mts", "cts", "js", "jsx", "mjs", "cjs"];
+
+// Files that mvts parses and rewrites imports in. Other files, such as svg or
+// css assets, are only renamed.
+pub fn is_source_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|suffix| suffix.to_str())
+        .map(|suffix| SOURCE_SUFFIXES.contains(&suffix))
+        .unwrap_or(false)
+}
+
+// Source files under dir. Dependencies in node_modules are skipped even when
+// no .gitignore excludes them, as outside of git repositories.
+pub fn iter_files(dir: &Path) -> impl Iterator<Item = PathBuf> {
+    let dir = dir.to_path_buf();
+    Walk::new(&dir)
         .filter_map(|result| result.ok())
-        .filter(|entry| {
-            entry
-                .file_name()
-                .to_str()
-                .map(|file_name| file_name.ends_with(".ts") || file_name.ends_with(".tsx"))
+        .filter(|entry| is_source_file(entry.path()))
+        .filter(move |entry| {
+            !entry
+                .path()
+                .strip_prefix(&dir)
+                .map(is_in_node_modules)
                 .unwrap_or(false)
         })
         .map(|entry| entry.path().to_path_buf())
 }
+
+// Every file under dir, regardless of suffix.
+pub fn iter_all_files(dir: &Path) -> impl Iterator<Item = PathBuf> {
+    Walk::new(dir)
+        .filter_map(|result| result.ok())
+        .filter(|entry| entry.file_type().map(|t| t.is_file()).unwrap_or(false))
+        .map(|entry| entry.path().to_path_buf())
+}
+
+// Every file under a moved folder. Hidden and ignored files move along with
+// the folder, so they are not skipped.
+pub fn iter_moved_files(dir: &Path) -> impl Iterator<Item = PathBuf> {
+    WalkBuilder::new(dir)
+        .standard_filters(false)
+        .build()
+        .filter_map(|result| result.ok())
+        .filter(|entry| entry.file_type().map(|t| t.is_file()).unwrap_or(false))
+        .map(|entry| entry.path().to_path_buf())
+}
+
+pub fn is_in_node_modules(path: &Path) -> bool {
+    path.components()
+        .any(|component| component == Component::Normal("node_modules".as_ref()))
+}
+
+pub fn is_glob(path: &Path) -> bool {
+    !path.exists()
+        && path
+            .to_str()
+            .map(|path| path.contains(&GLOB_CHARS[..]))
+            .unwrap_or(false)
+}
+
+// Finds the files matching a glob pattern, such as src/**/*.stories.tsx.
+// Returns the folder preceding the first wildcard along with the matches.
+pub fn glob_files(pattern: &Path) -> Result<(PathBuf, Vec<PathBuf>)> {
+    let pattern_str = pattern
+        .to_str()
+        .ok_or_else(|| anyhow!("Non utf-8 path {:?}", pattern))?;
+    let matcher = GlobBuilder::new(pattern_str)
+        .literal_separator(true)
+        .build()
+        .map_err(|err| anyhow!("Invalid glob {:?}: {}", pattern_str, err))?
+        .compile_matcher();
+
+    let base: PathBuf = pattern
+        .components()
+        .take_while(|component| match component {
+            Component::Normal(name) => name
+                .to_str()
+                .map(|name| !name.contains(&GLOB_CHARS[..]))
+                .unwrap_or(false),
+            _ => true,
+        })
+        .collect();
+
+    let files = iter_all_files(&base)
+        .filter(|file| matcher.is_match(file))
+        .collect();
+
+    Ok((base, files))
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use std::fs;
+    use std::path::PathBuf;
+
+    use crate::test_dir::TempDir;
+
+    #[test]
+    fn it_skips_node_modules() -> Result<()> {
+        let tmp = TempDir::new("grep");
+        fs::create_dir_all(tmp.join("src"))?;
+        fs::create_dir_all(tmp.join("node_modules/dep"))?;
+        fs::write(tmp.join("src/a.ts"), "")?;
+        fs::write(tmp.join("node_modules/dep/index.js"), "")?;
+
+        let files: Vec<PathBuf> = super::iter_files(&tmp).collect();
+        assert_eq!(files, vec![tmp.join("src/a.ts")]);
+
+        Ok(())
+    }
+}
@@ -1,9 +1,9 @@
 use anyhow::{anyhow, Result};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use crate::path;
 
-pub fn to_path(file: &PathBuf, import_string: &str) -> Result<PathBuf> {
+pub fn to_path(file: &Path, import_string: &str) -> Result<PathBuf> {
     let dir = path::get_parent(file);
     let import_path: PathBuf = import_string.into();
     let path = dir.join(import_path);
@@ -11,17 +11,18 @@ pub fn to_path(file: &PathBuf, import_string: &str) -> Result<PathBuf> {
     path::normalize(&path)
 }
 
-fn from_relative_path(rel_path: &PathBuf) -> Result<String> {
+fn from_relative_path(rel_path: &Path) -> Result<String> {
     let import_string = rel_path.to_str().ok_or_else(|| anyhow!("Non utf-8 path"))?;
 
-    Ok(match import_string.as_ref() {
-        x if x.starts_with('.') => x.into(),
+    Ok(match import_string {
+        // Dotfiles such as .env still need the ./ prefix.
+        x if x.starts_with("./") || x.starts_with("../") || x == ".." => x.into(),
         "" => ".".into(),
-        _ => "./".to_owned() + &import_string,
+        _ => "./".to_owned() + import_string,
     })
 }
 
-pub fn from_paths(file: &PathBuf, required_file: &PathBuf) -> Result<String> {
+pub fn from_paths(file: &Path, required_file: &Path) -> Result<String> {
     let file_dir = path::get_parent(file);
 
     let rel_path = path::diff(&file_dir, required_file)?;
@@ -45,25 +46,47 @@ pub fn to_node_import(import_sting: &str) -> &str {
         .unwrap_or(import_sting)
 }
 
+// With NodeNext module resolution imports name the emitted JavaScript file,
+// so ./foo.js refers to foo.ts or foo.tsx and ./foo.mjs to foo.mts.
+pub fn to_emitted_file(file: &Path) -> Option<PathBuf> {
+    let suffix = match file.extension()?.to_str()? {
+        "ts" | "tsx" => "js",
+        "mts" => "mjs",
+        "cts" => "cjs",
+        _ => return None,
+    };
+    Some(file.with_extension(suffix))
+}
+
+fn is_emitted_import_from(
+    source_file: &Path,
+    required_file: &Path,
+    import_string: &str,
+) -> Result<bool> {
+    match to_emitted_file(required_file) {
+        Some(emitted_file) => Ok(from_paths(source_file, &emitted_file)?.eq(import_string)),
+        None => Ok(false),
+    }
+}
+
 pub fn is_import_from(
-    source_file: &PathBuf,
-    required_file: &PathBuf,
+    source_file: &Path,
+    required_file: &Path,
     import_string: &str,
 ) -> Result<bool> {
-    let rel_string = from_paths(&source_file, &required_file)?;
+    let rel_string = from_paths(source_file, required_file)?;
     let wo_index = to_node_import(&rel_string);
     let with_index = wo_index.to_owned() + "/index";
 
-    Ok(
-        import_string.eq(&rel_string)
-            || import_string.eq(wo_index)
-            || import_string.eq(&with_index),
-    )
+    Ok(import_string.eq(&rel_string)
+        || import_string.eq(wo_index)
+        || import_string.eq(&with_index)
+        || is_emitted_import_from(source_file, required_file, import_string)?)
 }
 
 pub struct SourceFileRename<'a> {
-    pub old_location: &'a PathBuf,
-    pub new_location: &'a PathBuf,
+    pub old_location: &'a Path,
+    pub new_location: &'a Path,
     pub import_string: &'a str,
 }
 
@@ -79,10 +102,10 @@ pub fn rename_source_file(
 }
 
 pub struct RequiredFileRename<'a> {
-    pub source_file: &'a PathBuf,
+    pub source_file: &'a Path,
     pub import_string: &'a str,
-    pub old_location: &'a PathBuf,
-    pub new_location: &'a PathBuf,
+    pub old_location: &'a Path,
+    pub new_location: &'a Path,
 }
 
 pub fn rename_required_file(
@@ -97,6 +120,12 @@ pub fn rename_required_file(
         return Ok(import_string.to_string());
     }
 
+    if is_emitted_import_from(source_file, old_location, import_string)? {
+        if let Some(emitted_file) = to_emitted_file(new_location) {
+            return from_paths(source_file, &emitted_file);
+        }
+    }
+
     let suffix: PathBuf = import_string.into();
     let suffix = suffix.extension();
 
@@ -173,6 +202,7 @@ mod tests {
         rename_to_new_file_08: ("a/b/c/old.ts", "a/x/y/z/new.ts", "./s/t/u/file.jsx",  "../../../b/c/s/t/u/file.jsx"),
         rename_to_new_file_09: ("a/b/c/old.ts", "a/x/y/z/new.ts", "./s/t/u/file.svg",  "../../../b/c/s/t/u/file.svg"),
         rename_to_new_file_10: ("a/b/c/old.ts", "a/x/y/z/new.ts", "./s/t/u/index.ts",  "../../../b/c/s/t/u/index.ts"),
+        rename_to_new_file_11: ("a/b/old.ts", "a/new.ts", "../.env",  "./.env"),
     }
 
     macro_rules! rename_required_file_tests{
@@ -204,6 +234,10 @@ mod tests {
         rename_req_file_01: ("a/source.ts", "./b.ts", "a/b/index.ts",  "a/b/c/index.ts", "./b.ts"), // stays same
         rename_req_file_02: ("a/source.ts", "./b.ts", "a/b.ts",  "a/b/c/index.ts", "./b/c/index.ts"), // deno move
         rename_req_file_03: ("a/b/source.ts", "..", "a/index.ts",  "a/b/c/index.ts", "./c"), // node move
+        rename_req_file_04: ("a/source.ts", "./b.js", "a/b.ts",  "a/c/b.ts", "./c/b.js"), // nodenext move
+        rename_req_file_05: ("a/source.ts", "./b/index.js", "a/b/index.tsx",  "a/c.tsx", "./c.js"),
+        rename_req_file_06: ("a/source.mts", "../b.mjs", "b.mts",  "a/b.mts", "./b.mjs"),
+        rename_req_file_07: ("a/source.ts", "./b.js", "a/b.ts",  "a/c/b.js", "./c/b.js"),
     }
 
     macro_rules! is_relative_import_to_tests {
@@ -228,6 +262,11 @@ mod tests {
         is_relative_import_to_2: ("a/b/some_file.ts", "a/index.ts", "..", true),
         is_relative_import_to_3: ("a/b/some_file.ts", "a/index.ts", "../index.ts", true),
         is_relative_import_to_4: ("a/b/some_file.ts", "a/c/some.svg", "../c/some.svg", true),
+        is_relative_import_to_5: ("a/b/some_file.ts", "a/c/some.ts", "../c/some.js", true),
+        is_relative_import_to_6: ("a/b/some_file.ts", "a/c/some.tsx", "../c/some.js", true),
+        is_relative_import_to_7: ("a/b/some_file.ts", "a/c/some.cts", "../c/some.cjs", true),
+        is_relative_import_to_8: ("a/b/some_file.ts", "a/c/some.cts", "../c/some.js", false),
+        is_relative_import_to_9: ("a/b/some_file.ts", "a/c/some.js", "../c/some.ts", false),
     }
 
     macro_rules! gets_import_from_paths_tests {
@@ -0,0 +1,246 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::edit::TextEdit;
+use crate::plan::{FileEdit, MovePlan, Rename};
+use crate::root;
+use crate::transaction::{Step, Transaction};
+
+const JOURNAL_FILE: &str = "journal.json";
+
+// Record of the last applied operation, used by `mvts undo`.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Journal {
+    pub steps: Vec<Step>,
+}
+
+// Journals live in .git/mvts when inside a git repository, so they are never
+// committed by accident. Otherwise they live in .mvts in the folder mvts runs
+// from, as the project root found for a move may differ from the one found
+// for `mvts undo`.
+fn journal_file(current_dir: &Path, root: &Path) -> PathBuf {
+    match root::find_git_dir(root) {
+        Some(git_dir) => git_dir.join("mvts").join(JOURNAL_FILE),
+        None => current_dir.join(".mvts").join(JOURNAL_FILE),
+    }
+}
+
+// Undo may run from a subfolder of the one the move ran from, so .mvts
+// folders above current_dir are searched too.
+fn find_journal_file(current_dir: &Path, root: &Path) -> PathBuf {
+    if root::find_git_dir(root).is_some() {
+        return journal_file(current_dir, root);
+    }
+
+    current_dir
+        .ancestors()
+        .map(|dir| dir.join(".mvts").join(JOURNAL_FILE))
+        .find(|file| file.is_file())
+        .unwrap_or_else(|| journal_file(current_dir, root))
+}
+
+impl Journal {
+    pub fn save(&self, current_dir: &Path, root: &Path) -> Result<()> {
+        let file = journal_file(current_dir, root);
+        if let Some(dir) = file.parent() {
+            fs::create_dir_all(dir).map_err(|_| anyhow!("Failed to create {:?}", dir))?;
+        }
+        let contents = serde_json::to_string_pretty(self)?;
+        fs::write(&file, contents + "\n").map_err(|_| anyhow!("Failed to write {:?}", file))
+    }
+
+    pub fn load(current_dir: &Path, root: &Path) -> Result<(PathBuf, Self)> {
+        let file = find_journal_file(current_dir, root);
+        let contents = fs::read_to_string(&file)
+            .map_err(|_| anyhow!("Nothing to undo, {:?} does not exist", file))?;
+        let journal = serde_json::from_str(&contents)
+            .map_err(|err| anyhow!("Invalid journal {:?}: {}", file, err))?;
+        Ok((file, journal))
+    }
+
+    // Fails listing the written files that have been edited since, so that
+    // unrelated work is never overwritten.
+    fn check_unchanged(&self) -> Result<()> {
+        let changed: Vec<String> = self
+            .steps
+            .iter()
+            .filter_map(|step| match step {
+                Step::Wrote { path, contents, .. } => Some((path, contents)),
+                _ => None,
+            })
+            .filter(|(path, contents)| {
+                let current_path = self.current_location(path);
+                fs::read_to_string(current_path)
+                    .map(|current| !current.eq(*contents))
+                    .unwrap_or(true)
+            })
+            .map(|(path, _)| format!("{:?}", path))
+            .collect();
+
+        match changed.is_empty() {
+            true => Ok(()),
+            false => Err(anyhow!(
+                "Cannot undo, files have changed since:\n{}",
+                changed.join("\n")
+            )),
+        }
+    }
+
+    // Reverts the journaled steps. Nothing is reverted if any written file has
+    // been edited since.
+    pub fn undo(&self) -> Result<()> {
+        self.check_unchanged()?;
+
+        let mut transaction = Transaction::default();
+
+        let result = self.steps.iter().rev().try_for_each(|step| match step {
+            Step::Renamed { from, to } => transaction.rename(to, from),
+            Step::Wrote {
+                path,
+                original,
+                contents,
+            } => transaction.write(path, contents.clone(), original.clone()),
+            Step::CreatedDir { .. } => Ok(()),
+        });
+
+        if let Err(err) = result {
+            return match transaction.rollback() {
+                Ok(_) => Err(err.context("All changes were rolled back")),
+                Err(rollback_err) => Err(err.context(rollback_err)),
+            };
+        }
+
+        for step in self.steps.iter().rev() {
+            if let Step::CreatedDir { path } = step {
+                let _ = fs::remove_dir(path);
+            }
+        }
+
+        Ok(())
+    }
+
+    // The undo as a plan, so that `mvts undo --dry-run` can print it. Edits
+    // apply to the current locations, before the renames are reverted.
+    pub fn to_undo_plan(&self) -> Result<MovePlan> {
+        self.check_unchanged()?;
+
+        // Moves may pass through temporary names, which the plan leaves out.
+        let mut renames: Vec<Rename> = vec![];
+        for step in self.steps.iter().rev() {
+            if let Step::Renamed { from, to } = step {
+                match renames.iter_mut().find(|rename| rename.to.eq(to)) {
+                    Some(rename) => rename.to = from.clone(),
+                    None => renames.push(Rename {
+                        from: to.clone(),
+                        to: from.clone(),
+                    }),
+                }
+            }
+        }
+
+        let edits = self
+            .steps
+            .iter()
+            .filter_map(|step| match step {
+                Step::Wrote {
+                    path,
+                    original,
+                    contents,
+                } => Some(FileEdit {
+                    path: self.current_location(path),
+                    edits: vec![TextEdit {
+                        start: 0,
+                        end: contents.len(),
+                        old_text: contents.clone(),
+                        new_text: original.clone(),
+                    }],
+                }),
+                _ => None,
+            })
+            .collect();
+
+        Ok(MovePlan { renames, edits })
+    }
+
+    // Files are written before renames, so a written path may have moved
+    // afterwards, either directly or along with a renamed folder.
+    fn current_location(&self, path: &Path) -> PathBuf {
+        self.steps
+            .iter()
+            .fold(path.to_path_buf(), |current, step| match step {
+                Step::Renamed { from, to } => match current.strip_prefix(from) {
+                    Ok(rest) if rest.as_os_str().is_empty() => to.clone(),
+                    Ok(rest) => to.join(rest),
+                    Err(_) => current,
+                },
+                _ => current,
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use std::fs;
+
+    use crate::edit;
+    use crate::plan::Rename;
+    use crate::test_dir::TempDir;
+    use crate::transaction::Transaction;
+
+    #[test]
+    fn it_undoes_journaled_changes() -> Result<()> {
+        let tmp = TempDir::new("journal");
+        fs::create_dir_all(tmp.join("a"))?;
+        fs::write(tmp.join("a/a.ts"), "a")?;
+        fs::write(tmp.join("b.ts"), "b")?;
+
+        let mut transaction = Transaction::default();
+        transaction.write(&tmp.join("a/a.ts"), "a".into(), "changed a".into())?;
+        transaction.write(&tmp.join("b.ts"), "b".into(), "changed b".into())?;
+        transaction.rename(&tmp.join("a"), &tmp.join("x/y"))?;
+        transaction.into_journal().save(&tmp, &tmp.join("x/y"))?;
+
+        fs::write(tmp.join("b.ts"), "local edit")?;
+        let (_, journal) = super::Journal::load(&tmp.join("x"), &tmp.join("x"))?;
+        assert!(journal.undo().is_err());
+        assert!(tmp.join("x/y/a.ts").exists());
+
+        fs::write(tmp.join("b.ts"), "changed b")?;
+        journal.undo()?;
+
+        assert_eq!(fs::read_to_string(tmp.join("a/a.ts"))?, "a");
+        assert_eq!(fs::read_to_string(tmp.join("b.ts"))?, "b");
+        assert!(!tmp.join("x").exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_plans_undo_without_touching_files() -> Result<()> {
+        let tmp = TempDir::new("journal-plan");
+        fs::create_dir_all(tmp.join("a"))?;
+        fs::write(tmp.join("a/a.ts"), "a")?;
+
+        let mut transaction = Transaction::default();
+        transaction.write(&tmp.join("a/a.ts"), "a".into(), "changed a".into())?;
+        transaction.rename(&tmp.join("a"), &tmp.join(".a.mvts-0"))?;
+        transaction.rename(&tmp.join(".a.mvts-0"), &tmp.join("b"))?;
+        let plan = transaction.into_journal().to_undo_plan()?;
+
+        assert_eq!(
+            plan.renames,
+            vec![Rename {
+                from: tmp.join("b"),
+                to: tmp.join("a"),
+            }]
+        );
+        assert_eq!(plan.edits[0].path, tmp.join("b/a.ts"));
+        assert_eq!(edit::apply_edits("changed a", &plan.edits[0].edits)?, "a");
+        assert_eq!(fs::read_to_string(tmp.join("b/a.ts"))?, "changed a");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,279 @@
+use anyhow::{anyhow, Result};
+use serde::de::DeserializeOwned;
+
+// Blanks out comments and trailing commas so that byte offsets of the
+// remaining JSON stay the same as in the original source.
+pub fn strip_comments(source: &str) -> String {
+    let mut output: Vec<u8> = source.as_bytes().to_vec();
+    let bytes = source.as_bytes();
+    let mut idx = 0;
+    let mut pending_comma: Option<usize> = None;
+
+    while idx < bytes.len() {
+        match bytes[idx] {
+            b'"' => {
+                pending_comma = None;
+                idx += 1;
+                while idx < bytes.len() && bytes[idx] != b'"' {
+                    if bytes[idx] == b'\\' {
+                        idx += 1;
+                    }
+                    idx += 1;
+                }
+                idx += 1;
+            }
+            b'/' if bytes.get(idx + 1) == Some(&b'/') => {
+                while idx < bytes.len() && bytes[idx] != b'\n' {
+                    output[idx] = b' ';
+                    idx += 1;
+                }
+            }
+            b'/' if bytes.get(idx + 1) == Some(&b'*') => {
+                let end = source[idx + 2..]
+                    .find("*/")
+                    .map(|end| idx + 2 + end + 2)
+                    .unwrap_or_else(|| bytes.len());
+                for byte in output.iter_mut().take(end).skip(idx) {
+                    if *byte != b'\n' {
+                        *byte = b' ';
+                    }
+                }
+                idx = end;
+            }
+            b',' => {
+                pending_comma = Some(idx);
+                idx += 1;
+            }
+            b'}' | b']' => {
+                if let Some(comma) = pending_comma.take() {
+                    output[comma] = b' ';
+                }
+                idx += 1;
+            }
+            byte if byte.is_ascii_whitespace() => {
+                idx += 1;
+            }
+            _ => {
+                pending_comma = None;
+                idx += 1;
+            }
+        }
+    }
+
+    // Only ASCII bytes outside of strings were replaced, so this stays utf-8.
+    String::from_utf8(output).unwrap_or_else(|_| source.to_string())
+}
+
+pub fn parse<T: DeserializeOwned>(source: &str) -> Result<T> {
+    serde_json::from_str(&strip_comments(source)).map_err(|err| anyhow!("Invalid JSON: {}", err))
+}
+
+// A string value of a JSON document with the keys and array indices leading
+// to it, and its byte range without the quotes.
+#[derive(Debug, PartialEq)]
+pub struct JsonString {
+    pub keys: Vec<String>,
+    pub start: usize,
+    pub end: usize,
+    pub value: String,
+}
+
+struct Walker<'a> {
+    source: &'a str,
+    idx: usize,
+    strings: Vec<JsonString>,
+}
+
+impl<'a> Walker<'a> {
+    fn skip_whitespace(&mut self) {
+        let bytes = self.source.as_bytes();
+        while self.idx < bytes.len() && bytes[self.idx].is_ascii_whitespace() {
+            self.idx += 1;
+        }
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<()> {
+        self.skip_whitespace();
+        match self.source.as_bytes().get(self.idx) {
+            Some(found) if *found == byte => {
+                self.idx += 1;
+                Ok(())
+            }
+            _ => Err(anyhow!(
+                "Invalid JSON: expected {:?} at {}",
+                byte as char,
+                self.idx
+            )),
+        }
+    }
+
+    fn unexpected(&self) -> anyhow::Error {
+        match self.source.as_bytes().get(self.idx) {
+            Some(byte) => anyhow!(
+                "Invalid JSON: unexpected {:?} at {}",
+                *byte as char,
+                self.idx
+            ),
+            None => anyhow!("Invalid JSON: unexpected end"),
+        }
+    }
+
+    // Returns the byte range of the string starting at idx and its value.
+    fn string(&mut self) -> Result<(usize, usize, String)> {
+        let bytes = self.source.as_bytes();
+        let start = self.idx + 1;
+        let mut end = start;
+        while end < bytes.len() && bytes[end] != b'"' {
+            if bytes[end] == b'\\' {
+                end += 1;
+            }
+            end += 1;
+        }
+        if end >= bytes.len() {
+            return Err(anyhow!("Invalid JSON: unterminated string at {}", self.idx));
+        }
+        self.idx = end + 1;
+        let value = serde_json::from_str(&self.source[start - 1..end + 1])
+            .map_err(|err| anyhow!("Invalid JSON: {}", err))?;
+        Ok((start, end, value))
+    }
+
+    fn value(&mut self, keys: &mut Vec<String>) -> Result<()> {
+        self.skip_whitespace();
+        let bytes = self.source.as_bytes();
+
+        match bytes.get(self.idx) {
+            Some(b'{') => {
+                self.idx += 1;
+                self.skip_whitespace();
+                while bytes.get(self.idx) != Some(&b'}') {
+                    self.skip_whitespace();
+                    if bytes.get(self.idx) == Some(&b']') {
+                        return Err(self.unexpected());
+                    }
+                    if bytes.get(self.idx) != Some(&b'"') {
+                        return Err(anyhow!("Invalid JSON: expected key at {}", self.idx));
+                    }
+                    let (_, _, key) = self.string()?;
+                    self.expect(b':')?;
+                    keys.push(key);
+                    self.value(keys)?;
+                    keys.pop();
+                    self.skip_whitespace();
+                    if bytes.get(self.idx) == Some(&b',') {
+                        self.idx += 1;
+                        self.skip_whitespace();
+                    }
+                }
+                self.expect(b'}')
+            }
+            Some(b'[') => {
+                self.idx += 1;
+                self.skip_whitespace();
+                let mut idx = 0;
+                while bytes.get(self.idx) != Some(&b']') {
+                    if bytes.get(self.idx) == Some(&b'}') {
+                        return Err(self.unexpected());
+                    }
+                    keys.push(idx.to_string());
+                    self.value(keys)?;
+                    keys.pop();
+                    idx += 1;
+                    self.skip_whitespace();
+                    if bytes.get(self.idx) == Some(&b',') {
+                        self.idx += 1;
+                        self.skip_whitespace();
+                    }
+                }
+                self.expect(b']')
+            }
+            Some(b'"') => {
+                let (start, end, value) = self.string()?;
+                self.strings.push(JsonString {
+                    keys: keys.clone(),
+                    start,
+                    end,
+                    value,
+                });
+                Ok(())
+            }
+            Some(_) => {
+                // Numbers, booleans and null. Anything else, such as a stray
+                // bracket, would otherwise leave idx in place forever.
+                let start = self.idx;
+                while self.idx < bytes.len() && !b",]} \t\r\n".contains(&bytes[self.idx]) {
+                    self.idx += 1;
+                }
+                match self.idx > start {
+                    true => Ok(()),
+                    false => Err(self.unexpected()),
+                }
+            }
+            None => Err(anyhow!("Invalid JSON: unexpected end")),
+        }
+    }
+}
+
+// Finds every string value in a JSON document with comments, so that single
+// values can be rewritten without reformatting the document.
+pub fn strings(source: &str) -> Result<Vec<JsonString>> {
+    let stripped = strip_comments(source);
+    let mut walker = Walker {
+        source: &stripped,
+        idx: 0,
+        strings: vec![],
+    };
+    walker.value(&mut vec![])?;
+    Ok(walker.strings)
+}
+
+#[cfg(test)]
+mod tests {
+    macro_rules! strip_comments_tests {
+        ($($name:ident: $value:expr,)*) => {
+        $(
+            #[test]
+            fn $name() {
+                let (input, expected) = $value;
+                let result = super::strip_comments(input);
+                assert_eq!(result, expected);
+            }
+        )*
+        }
+    }
+
+    strip_comments_tests! {
+        strip_comments_0: (r#"{"a": 1} // end"#, r#"{"a": 1}       "#),
+        strip_comments_1: ("{/* x */\"a\": 1}", "{       \"a\": 1}"),
+        strip_comments_2: (r#"{"a": "// not a comment"}"#, r#"{"a": "// not a comment"}"#),
+        strip_comments_3: (r#"{"a": [1, 2,], }"#, r#"{"a": [1, 2 ]  }"#),
+        strip_comments_4: ("{\"a\": 1, // x\n}", "{\"a\": 1      \n}"),
+    }
+
+    #[test]
+    fn it_finds_string_values() -> anyhow::Result<()> {
+        let source = "{\n  // entry\n  \"main\": \"./a.js\",\n  \"n\": [1, true, null, {\"x\": \"b\\\"c\"}],\n}";
+        let strings = super::strings(source)?;
+
+        assert_eq!(strings.len(), 2);
+        assert_eq!(strings[0].keys, vec!["main"]);
+        assert_eq!(&source[strings[0].start..strings[0].end], "./a.js");
+        assert_eq!(strings[1].keys, vec!["n", "3", "x"]);
+        assert_eq!(&source[strings[1].start..strings[1].end], "b\\\"c");
+        assert_eq!(strings[1].value, "b\"c");
+        Ok(())
+    }
+
+    #[test]
+    fn it_rejects_mismatched_brackets() {
+        for source in [
+            r#"{"files": ["a.ts"}"#,
+            r#"{"files": ["a.ts"]]"#,
+            r#"{"main": }"#,
+            r#"["a", }]"#,
+            r#"{"a": [1, 2"#,
+        ] {
+            assert!(super::strings(source).is_err(), "{}", source);
+        }
+    }
+}
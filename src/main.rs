@@ -1,217 +1,282 @@
 use anyhow::{anyhow, Result};
-use rayon::iter::{IntoParallelIterator, ParallelIterator};
 use std::env;
 use std::fs;
-use std::path::PathBuf;
-use std::thread;
+use std::path::{Path, PathBuf};
 use structopt::StructOpt;
 
+mod case;
+mod diff;
 mod edit;
 mod grep;
 mod import_string;
+mod journal;
+mod jsonc;
+mod manifest;
+mod normalize;
+mod package_json;
 mod parser;
 mod path;
+mod plan;
+mod rename;
+mod resolver;
+mod root;
+#[cfg(test)]
+mod test_dir;
+mod transaction;
+mod tsconfig;
+mod workspace;
+
+use case::Case;
+use journal::Journal;
+use normalize::Extensions;
+use plan::{MovePlan, Rename};
+use rename::Substitution;
+use resolver::Resolver;
+use workspace::Workspace;
 
 #[derive(StructOpt)]
 struct Cli {
+    /// Project root to search for affected files. Defaults to the nearest
+    /// folder above the source containing package.json, tsconfig.json or .git
+    #[structopt(long, global = true, parse(from_os_str))]
+    root: Option<PathBuf>,
+    /// Print planned changes as a unified diff instead of applying them
+    #[structopt(long, global = true)]
+    dry_run: bool,
+    /// Write planned changes as JSON to this file instead of applying them
+    #[structopt(long, global = true, parse(from_os_str))]
+    plan: Option<PathBuf>,
+    /// Move files matched by glob patterns directly into the target folder
+    /// instead of preserving their folders relative to the pattern
+    #[structopt(long)]
+    flatten: bool,
+    /// Move every pair of old and new paths listed in this CSV, TSV or JSON
+    /// file as one operation. Use - to read the list from stdin
+    #[structopt(long, parse(from_os_str))]
+    from: Option<PathBuf>,
+    /// Source files, folders or quoted glob patterns followed by the target.
+    /// With several sources the target must be an existing folder, like with mv
     #[structopt(parse(from_os_str))]
-    source_path: PathBuf,
-    #[structopt(parse(from_os_str))]
-    target_path: PathBuf,
+    paths: Vec<PathBuf>,
+    #[structopt(subcommand)]
+    command: Option<Command>,
 }
 
-fn main() -> Result<()> {
-    let Cli {
-        source_path,
-        target_path,
-    } = Cli::from_args();
-
-    let current_dir = env::current_dir()?;
+#[derive(StructOpt)]
+enum Command {
+    /// Apply a plan written with --plan
+    Apply {
+        #[structopt(parse(from_os_str))]
+        plan_file: PathBuf,
+    },
+    /// Revert the last move, refusing if files were edited since
+    Undo,
+    /// Rename every source file under a folder whose path, relative to the
+    /// folder, matches a sed style substitution, or convert file names to a
+    /// naming convention
+    Rename {
+        /// Substitution such as 's/Container(\.tsx?)$/View$1/'
+        #[structopt(long, required_unless = "case", conflicts_with = "case")]
+        regex: Option<String>,
+        /// Naming convention for file names
+        #[structopt(long, possible_values = &case::CASES, case_insensitive = true)]
+        case: Option<Case>,
+        #[structopt(parse(from_os_str))]
+        dir: PathBuf,
+    },
+    /// Rewrite every relative import in the project to name the emitted .js
+    /// file, the .ts file itself, or no extension at all
+    Normalize {
+        #[structopt(long, possible_values = &normalize::EXTENSIONS, case_insensitive = true)]
+        extensions: Extensions,
+    },
+}
 
-    if source_path.is_dir() {
-        rename_dir(current_dir, source_path, target_path)
-    } else {
-        rename_single_file(current_dir, source_path, target_path)
-    }
+fn find_root(current_dir: &Path, root: Option<PathBuf>, start: &Path) -> Result<PathBuf> {
+    Ok(match root {
+        Some(root) => path::join(current_dir, &root)?,
+        None => root::find(start).unwrap_or_else(|| current_dir.to_path_buf()),
+    })
 }
 
-fn rename_single_file(
-    current_dir: PathBuf,
-    source_path: PathBuf,
-    target_path: PathBuf,
+// Plans the renames and outputs the plan.
+fn run(
+    current_dir: &Path,
+    root: &Path,
+    renames: Vec<Rename>,
+    dry_run: bool,
+    plan: Option<PathBuf>,
 ) -> Result<()> {
-    let mut target_file = target_path;
-
-    if target_file.is_dir() {
-        let file_name = source_path.file_name().unwrap();
-        target_file.push(file_name);
+    let workspace = Workspace::load(root)?;
+    let mut resolver = Resolver::load(root)?;
+    resolver.add_packages(&workspace.packages)?;
+    resolver.add_subpath_imports(root)?;
+    let move_plan = plan::plan_moves(root, renames, &resolver)?;
+
+    for new_dependency in workspace.new_dependencies(&move_plan) {
+        let file = path::diff(current_dir, &new_dependency.file)?;
+        eprintln!(
+            "{:?} now imports {}, which is not a dependency of {}",
+            file, new_dependency.dependency, new_dependency.package
+        );
     }
 
-    let full_source_path = path::join(&current_dir, &source_path)?;
-    let full_target_path = path::join(&current_dir, &target_file)?;
-
-    let source = source_path.clone();
-    let handler = thread::spawn(move || match move_file(&source, &target_file) {
-        Ok(_) => (),
-        Err(err) => println!("{:?}", err),
-    });
+    output(current_dir, root, move_plan, dry_run, plan)
+}
 
-    let other_files: Vec<PathBuf> = grep::iter_files(&current_dir)
-        .filter(|path| !path.eq(&full_target_path) && !path.eq(&full_source_path))
-        .collect();
+// Prints, saves or applies a plan.
+fn output(
+    current_dir: &Path,
+    root: &Path,
+    move_plan: MovePlan,
+    dry_run: bool,
+    plan: Option<PathBuf>,
+) -> Result<()> {
+    if dry_run {
+        print!("{}", diff::unified_diff(current_dir, &move_plan)?);
+        return Ok(());
+    }
 
-    other_files
-        .into_par_iter()
-        .try_for_each(move |affected_file| -> Result<()> {
-            let affected_file = path::join(&current_dir, &affected_file)?;
+    if let Some(plan) = plan {
+        return move_plan.save(&path::join(current_dir, &plan)?);
+    }
 
-            let source_code = fs::read_to_string(&affected_file)
-                .map_err(|_| anyhow!("Could not find {:?}", affected_file))?;
+    move_plan.apply()?.save(current_dir, root)
+}
 
-            let import_string = import_string::from_paths(&affected_file, &full_source_path)?;
-            let import_string = import_string::to_node_import(&import_string);
+fn main() -> Result<()> {
+    let Cli {
+        root,
+        dry_run,
+        plan,
+        flatten,
+        from,
+        paths,
+        command,
+    } = Cli::from_args();
 
-            let contains_import = source_code.contains(&import_string);
+    let current_dir = env::current_dir()?;
 
-            if !contains_import {
+    match command {
+        Some(Command::Apply { .. } | Command::Undo) if plan.is_some() => {
+            return Err(anyhow!("--plan cannot be used with apply or undo"));
+        }
+        Some(Command::Apply { plan_file }) => {
+            let root = find_root(&current_dir, root, &current_dir)?;
+            let move_plan = MovePlan::load(&path::join(&current_dir, &plan_file)?)?;
+            return output(&current_dir, &root, move_plan, dry_run, None);
+        }
+        Some(Command::Undo) => {
+            let root = find_root(&current_dir, root, &current_dir)?;
+            let (journal_file, journal) = Journal::load(&current_dir, &root)?;
+            if dry_run {
+                print!(
+                    "{}",
+                    diff::unified_diff(&current_dir, &journal.to_undo_plan()?)?
+                );
                 return Ok(());
             }
+            journal.undo()?;
+            return fs::remove_file(&journal_file)
+                .map_err(|_| anyhow!("Failed to remove {:?}", journal_file));
+        }
+        Some(Command::Rename { regex, case, dir }) => {
+            let dir = path::join(&current_dir, &dir)?;
+            let renames = match (regex, case) {
+                (Some(regex), _) => {
+                    let substitution = Substitution::parse(&regex)?;
+                    rename::to_renames(&dir, |path| substitution.apply(path))?
+                }
+                (None, Some(case)) => rename::to_renames(&dir, |path| rename::to_case(path, case))?,
+                (None, None) => return Err(anyhow!("Expected --regex or --case")),
+            };
+            let root = find_root(&current_dir, root, &dir)?;
+            return run(&current_dir, &root, renames, dry_run, plan);
+        }
+        Some(Command::Normalize { extensions }) => {
+            let root = find_root(&current_dir, root, &current_dir)?;
+            let move_plan = normalize::plan_normalize(&root, extensions)?;
+            return output(&current_dir, &root, move_plan, dry_run, plan);
+        }
+        None => (),
+    }
 
-            let updated_source_code = edit::move_required_file(
-                &source_code,
-                &affected_file,
-                &full_source_path,
-                &full_target_path,
-            )?;
+    let mut paths = paths
+        .iter()
+        .map(|path| path::join(&current_dir, path))
+        .collect::<Result<Vec<PathBuf>>>()?;
 
-            if !source_code.eq(&updated_source_code) {
-                fs::write(&affected_file, updated_source_code)
-                    .map_err(|_| anyhow!("Failed to write {:?}", affected_file))?
+    let (start, renames) = match from {
+        Some(manifest_file) => {
+            if !paths.is_empty() {
+                return Err(anyhow!("Expected either --from or paths, not both"));
             }
-
-            Ok(())
-        })?;
-
-    handler.join().unwrap();
-    Ok(())
-}
-
-fn move_file(source_path: &PathBuf, target_file: &PathBuf) -> Result<()> {
-    fs::rename(&source_path, &target_file)?;
-    let source_code = fs::read_to_string(&target_file)?;
-    let new_source_code = edit::move_source_file(source_code, &source_path, &target_file)?;
-    fs::write(target_file, new_source_code)?;
-    Ok(())
-}
-
-fn rename_dir(current_dir: PathBuf, source_path: PathBuf, target_path: PathBuf) -> Result<()> {
-    let full_source_path = path::join(&current_dir, &source_path)?;
-    let full_target_path = path::join(&current_dir, &target_path)?;
-
-    let moved_files: Result<Vec<(PathBuf, PathBuf)>> = grep::iter_files(&full_source_path)
-        .map(|file| {
-            let rel_path = path::diff(&full_source_path, &file)?;
-            let new_file = path::join(&full_target_path, &rel_path)?;
-            Ok((file, new_file))
-        })
-        .collect();
-
-    let moved_files = &moved_files?;
-
-    moved_files
-        .into_par_iter()
-        .try_for_each(|(source_file, target_file)| -> Result<()> {
-            let source_code = fs::read_to_string(&source_file)
-                .map_err(|_| anyhow!("Failed to read {:?}", source_file))?;
-
-            let new_source_code =
-                edit::replace_imports(&source_file, &source_code, |import_string| {
-                    let has_moved = moved_files.into_iter().find(|(moved_file, _)| {
-                        import_string::is_import_from(&source_file, moved_file, &import_string)
-                            .unwrap_or(false)
-                    });
-
-                    match has_moved {
-                        Some((old_location, new_location)) => {
-                            let args = import_string::RequiredFileRename {
-                                source_file: &source_file,
-                                import_string,
-                                old_location: &old_location,
-                                new_location: &new_location,
-                            };
-                            let import_string = import_string::rename_required_file(&args)?;
-                            let args = import_string::SourceFileRename {
-                                import_string: &&import_string,
-                                old_location: &source_file,
-                                new_location: &target_file,
-                            };
-                            import_string::rename_source_file(&args)
-                        }
-                        None => {
-                            let args = import_string::SourceFileRename {
-                                import_string: &&import_string,
-                                old_location: &source_file,
-                                new_location: &target_file,
-                            };
-                            import_string::rename_source_file(&args)
-                        }
-                    }
-                })?;
-
-            if !source_code.eq(&new_source_code) {
-                fs::write(source_file, new_source_code)
-                    .map_err(|_| anyhow!("Failed to write {:?}", source_file))?;
-            }
-
-            Ok(())
-        })?;
-
-    let other_files: Vec<PathBuf> = grep::iter_files(&current_dir)
-        .filter(|path| {
-            moved_files
+            let pairs = manifest::read(&manifest_file)?
                 .into_iter()
-                .find(|(moved_path, _)| moved_path.eq(path))
-                .is_none()
-        })
-        .collect();
-
-    other_files
-        .into_par_iter()
-        .try_for_each(|source_file| -> Result<()> {
-            let source_code = fs::read_to_string(&source_file)
-                .map_err(|_| anyhow!("Failed to read {:?}", source_file))?;
-
-            let new_source_code =
-                edit::replace_imports(&source_file, &source_code, |import_string| {
-                    let has_moved = moved_files.into_iter().find(|(moved_file, _)| {
-                        import_string::is_import_from(&source_file, moved_file, &import_string)
-                            .unwrap_or(false)
-                    });
-
-                    match has_moved {
-                        Some((old_location, new_location)) => {
-                            let args = import_string::RequiredFileRename {
-                                source_file: &source_file,
-                                import_string,
-                                old_location: &old_location,
-                                new_location: &new_location,
-                            };
-                            import_string::rename_required_file(&args)
-                        }
-                        None => Ok(import_string.clone()),
-                    }
-                })?;
-
-            if !source_code.eq(&new_source_code) {
-                fs::write(&source_file, new_source_code)
-                    .map_err(|_| anyhow!("Failed to write {:?}", source_file))?;
-            }
-
-            Ok(())
-        })?;
-
-    fs::rename(&source_path, &target_path)
-        .map_err(|_| anyhow!("Failed to rename {:?} to {:?}", &source_path, &target_path,))?;
+                .map(|(from, to)| {
+                    Ok((
+                        path::join(&current_dir, &from)?,
+                        path::join(&current_dir, &to)?,
+                    ))
+                })
+                .collect::<Result<_>>()?;
+            let renames = plan::to_manifest_renames(pairs)?;
+            (renames[0].from.clone(), renames)
+        }
+        None => {
+            let target_path = paths
+                .pop()
+                .filter(|_| !paths.is_empty())
+                .ok_or_else(|| anyhow!("Expected at least one source and a target path"))?;
+            let start = match grep::is_glob(&paths[0]) {
+                true => current_dir.clone(),
+                false => paths[0].clone(),
+            };
+            (start, plan::to_renames(paths, target_path, flatten)?)
+        }
+    };
+
+    let root = find_root(&current_dir, root, &start)?;
+    run(&current_dir, &root, renames, dry_run, plan)
+}
 
-    Ok(())
+#[cfg(test)]
+mod tests {
+    use structopt::StructOpt;
+
+    use super::{Cli, Command};
+
+    #[test]
+    fn it_accepts_global_flags_after_subcommands() {
+        let args = [
+            "mvts",
+            "rename",
+            "--case",
+            "kebab",
+            "src",
+            "--dry-run",
+            "--root",
+            ".",
+        ];
+        let cli = Cli::from_iter_safe(args.iter()).unwrap();
+        assert!(cli.dry_run);
+        assert!(cli.root.is_some());
+        assert!(matches!(cli.command, Some(Command::Rename { .. })));
+
+        let args = [
+            "mvts",
+            "normalize",
+            "--extensions",
+            "js",
+            "--plan",
+            "plan.json",
+        ];
+        let cli = Cli::from_iter_safe(args.iter()).unwrap();
+        assert!(cli.plan.is_some());
+
+        let args = ["mvts", "--dry-run", "a.ts", "b.ts"];
+        let cli = Cli::from_iter_safe(args.iter()).unwrap();
+        assert!(cli.dry_run);
+        assert_eq!(cli.paths.len(), 2);
+    }
 }
@@ -0,0 +1,117 @@
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum JsonEntry {
+    Pair(PathBuf, PathBuf),
+    Object { from: PathBuf, to: PathBuf },
+}
+
+const HEADERS: [(&str, &str); 3] = [("from", "to"), ("old", "new"), ("source", "target")];
+
+fn is_header(from: &str, to: &str) -> bool {
+    HEADERS.iter().any(|(from_header, to_header)| {
+        from.trim().eq_ignore_ascii_case(from_header) && to.trim().eq_ignore_ascii_case(to_header)
+    })
+}
+
+fn parse_json(contents: &str) -> Result<Vec<(PathBuf, PathBuf)>> {
+    let entries: Vec<JsonEntry> =
+        serde_json::from_str(contents).map_err(|err| anyhow!("Invalid JSON manifest: {}", err))?;
+
+    Ok(entries
+        .into_iter()
+        .map(|entry| match entry {
+            JsonEntry::Pair(from, to) => (from, to),
+            JsonEntry::Object { from, to } => (from, to),
+        })
+        .collect())
+}
+
+// Parses comma or tab separated old and new paths, one pair per line.
+fn parse_delimited(contents: &str) -> Result<Vec<(PathBuf, PathBuf)>> {
+    let first_line = contents.lines().find(|line| !line.trim().is_empty());
+    let delimiter = match first_line {
+        Some(line) if line.contains('\t') => b'\t',
+        _ => b',',
+    };
+
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .delimiter(delimiter)
+        .trim(csv::Trim::All)
+        .from_reader(contents.as_bytes());
+
+    let mut pairs = vec![];
+
+    for (idx, record) in reader.records().enumerate() {
+        let record = record.map_err(|err| anyhow!("Invalid manifest: {}", err))?;
+        let fields: Vec<&str> = record.iter().filter(|field| !field.is_empty()).collect();
+
+        match fields.as_slice() {
+            [] => continue,
+            [from, to] if idx == 0 && is_header(from, to) => continue,
+            [from, to] => pairs.push((from.into(), to.into())),
+            _ => return Err(anyhow!("Expected old and new path on line {}", idx + 1)),
+        }
+    }
+
+    Ok(pairs)
+}
+
+pub fn parse(contents: &str) -> Result<Vec<(PathBuf, PathBuf)>> {
+    match contents.trim_start().starts_with('[') {
+        true => parse_json(contents),
+        false => parse_delimited(contents),
+    }
+}
+
+// Reads a manifest of old and new paths from a file, or from stdin for "-".
+pub fn read(file: &Path) -> Result<Vec<(PathBuf, PathBuf)>> {
+    let contents = match file.to_str() {
+        Some("-") => {
+            let mut contents = String::new();
+            io::stdin().read_to_string(&mut contents)?;
+            contents
+        }
+        _ => fs::read_to_string(file).map_err(|_| anyhow!("Failed to read {:?}", file))?,
+    };
+
+    parse(&contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    macro_rules! parse_tests {
+        ($($name:ident: $value:expr,)*) => {
+        $(
+            #[test]
+            fn $name() {
+                let (input, expected) = $value;
+                let expected: Vec<(PathBuf, PathBuf)> = expected
+                    .iter()
+                    .map(|(from, to): &(&str, &str)| (from.into(), to.into()))
+                    .collect();
+
+                let result = super::parse(input).unwrap();
+                assert_eq!(result, expected);
+            }
+        )*
+        }
+    }
+
+    parse_tests! {
+        parse_csv_0: ("a.ts,b.ts\nc.ts,d.ts\n", [("a.ts", "b.ts"), ("c.ts", "d.ts")]),
+        parse_csv_1: ("old,new\n\"src/a, b.ts\",src/c.ts\n", [("src/a, b.ts", "src/c.ts")]),
+        parse_csv_2: ("From, To\r\n a.ts , b.ts \r\n\r\n", [("a.ts", "b.ts")]),
+        parse_tsv_0: ("a.ts\tb.ts\nb.ts\ta.ts\n", [("a.ts", "b.ts"), ("b.ts", "a.ts")]),
+        parse_json_0: (r#"[["a.ts", "b.ts"]]"#, [("a.ts", "b.ts")]),
+        parse_json_1: (r#"[{ "from": "a.ts", "to": "b.ts" }]"#, [("a.ts", "b.ts")]),
+    }
+}
@@ -0,0 +1,234 @@
+use anyhow::{anyhow, Error, Result};
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::edit;
+use crate::grep;
+use crate::import_string;
+use crate::plan::{FileEdit, MovePlan};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Extensions {
+    // Name the emitted file, like NodeNext resolution requires.
+    Js,
+    // Name the source file itself, for allowImportingTsExtensions.
+    Ts,
+    // Leave extensions and index files out, like bundlers allow.
+    None,
+}
+
+pub const EXTENSIONS: [&str; 3] = ["js", "ts", "none"];
+
+impl FromStr for Extensions {
+    type Err = Error;
+
+    fn from_str(name: &str) -> Result<Self> {
+        match name.to_lowercase().as_str() {
+            "js" => Ok(Extensions::Js),
+            "ts" => Ok(Extensions::Ts),
+            "none" => Ok(Extensions::None),
+            _ => Err(anyhow!(
+                "Unknown extensions {:?}, expected one of {:?}",
+                name,
+                EXTENSIONS
+            )),
+        }
+    }
+}
+
+const RESOLVED_SUFFIXES: [&str; 5] = ["ts", "tsx", "d.ts", "js", "jsx"];
+
+// Source files that an import of an emitted file may refer to.
+fn to_source_suffixes(suffix: &str) -> &'static [&'static str] {
+    match suffix {
+        "js" => &["ts", "tsx", "d.ts"],
+        "jsx" => &["tsx"],
+        "mjs" => &["mts", "d.mts"],
+        "cjs" => &["cts", "d.cts"],
+        _ => &[],
+    }
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(".");
+    path.push(suffix);
+    path.into()
+}
+
+// Finds the file a relative import refers to, the way TypeScript does: the
+// path itself, an emitted file's source, the path with a suffix, and finally
+// an index file in the folder.
+pub fn resolve(source_file: &Path, import_string: &str) -> Option<PathBuf> {
+    let path = import_string::to_path(source_file, import_string).ok()?;
+
+    if path.is_file() {
+        return Some(path);
+    }
+
+    let emitted_sources = path
+        .extension()
+        .and_then(|suffix| suffix.to_str())
+        .map(to_source_suffixes)
+        .unwrap_or_default()
+        .iter()
+        .map(|suffix| with_suffix(&path.with_extension(""), suffix));
+
+    let candidates = RESOLVED_SUFFIXES
+        .iter()
+        .map(|suffix| with_suffix(&path, suffix))
+        .chain(
+            RESOLVED_SUFFIXES
+                .iter()
+                .map(|suffix| with_suffix(&path.join("index"), suffix)),
+        );
+
+    emitted_sources
+        .chain(candidates)
+        .find(|candidate| candidate.is_file())
+}
+
+impl Extensions {
+    // Returns None when the style cannot be expressed for this file, for
+    // example a .mts file without an extension, or for imports of assets.
+    pub fn to_import(self, source_file: &Path, required_file: &Path) -> Option<String> {
+        if !grep::is_source_file(required_file) {
+            return None;
+        }
+
+        // Declaration files are imported like the JavaScript file they
+        // describe, never with their own suffix.
+        let file_name = required_file.file_name()?.to_str()?;
+        let declared_file = [".d.ts", ".d.mts", ".d.cts"]
+            .iter()
+            .find_map(|suffix| {
+                file_name
+                    .strip_suffix(suffix)
+                    .map(|stem| (stem, &suffix[2..]))
+            })
+            .map(|(stem, suffix)| required_file.with_file_name(stem.to_owned() + suffix));
+        let required_file = match (&declared_file, self) {
+            (Some(_), Extensions::Ts) => return None,
+            (Some(declared_file), _) => declared_file,
+            (None, _) => required_file,
+        };
+
+        let rel_string = import_string::from_paths(source_file, required_file).ok()?;
+
+        match self {
+            Extensions::Ts => Some(rel_string),
+            Extensions::Js => {
+                let emitted_file = import_string::to_emitted_file(required_file)
+                    .unwrap_or_else(|| required_file.to_path_buf());
+                import_string::from_paths(source_file, &emitted_file).ok()
+            }
+            Extensions::None => {
+                let node_import = import_string::to_node_import(&rel_string);
+                match node_import.eq(&rel_string) {
+                    true => None,
+                    false => Some(node_import.to_owned()).filter(|import| !import.is_empty()),
+                }
+            }
+        }
+    }
+}
+
+// Plans rewriting every relative import under root to the chosen style.
+// Imports that do not resolve to a file are left alone.
+pub fn plan_normalize(root: &Path, extensions: Extensions) -> Result<MovePlan> {
+    let files: Vec<PathBuf> = grep::iter_files(root).collect();
+
+    let edits: Vec<Option<FileEdit>> = files
+        .into_par_iter()
+        .map(|source_file| -> Result<Option<FileEdit>> {
+            let source_code = fs::read_to_string(&source_file)
+                .map_err(|_| anyhow!("Failed to read {:?}", source_file))?;
+
+            let edits = edit::replace_imports(&source_file, &source_code, |import_string| {
+                if !import_string.starts_with('.') {
+                    return Ok(import_string.clone());
+                }
+
+                Ok(resolve(&source_file, import_string)
+                    .and_then(|required_file| extensions.to_import(&source_file, &required_file))
+                    .unwrap_or_else(|| import_string.clone()))
+            })?;
+
+            Ok(match edits.is_empty() {
+                true => None,
+                false => Some(FileEdit {
+                    path: source_file,
+                    edits,
+                }),
+            })
+        })
+        .collect::<Result<_>>()?;
+
+    Ok(MovePlan {
+        renames: vec![],
+        edits: edits.into_iter().flatten().collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use std::fs;
+
+    use super::Extensions;
+    use crate::test_dir::TempDir;
+
+    #[test]
+    fn it_normalizes_extensions() -> Result<()> {
+        let tmp = TempDir::new("normalize");
+        fs::create_dir_all(tmp.join("utils"))?;
+        fs::write(tmp.join("a.ts"), "")?;
+        fs::write(tmp.join("b.tsx"), "")?;
+        fs::write(tmp.join("c.mts"), "")?;
+        fs::write(tmp.join("legacy.js"), "")?;
+        fs::write(tmp.join("types.d.ts"), "")?;
+        fs::write(tmp.join("logo.svg"), "")?;
+        fs::write(tmp.join("utils/index.ts"), "")?;
+
+        let code = r#"import './a';
+import './b.js';
+import './c.mjs';
+import './legacy';
+import './logo.svg';
+import './utils';
+import './types';
+import './missing';
+import 'react';
+"#;
+        fs::write(tmp.join("main.ts"), code)?;
+
+        let expected = [
+            (
+                Extensions::Js,
+                "import './a.js';\nimport './b.js';\nimport './c.mjs';\nimport './legacy.js';\nimport './logo.svg';\nimport './utils/index.js';\nimport './types.js';\n",
+            ),
+            (
+                Extensions::Ts,
+                "import './a.ts';\nimport './b.tsx';\nimport './c.mts';\nimport './legacy.js';\nimport './logo.svg';\nimport './utils/index.ts';\nimport './types';\n",
+            ),
+            (
+                Extensions::None,
+                "import './a';\nimport './b';\nimport './c.mjs';\nimport './legacy';\nimport './logo.svg';\nimport './utils';\nimport './types';\n",
+            ),
+        ];
+
+        for (extensions, expected) in expected.iter() {
+            let plan = super::plan_normalize(&tmp, *extensions)?;
+            let result = match plan.edits.first() {
+                Some(file_edit) => crate::edit::apply_edits(code, &file_edit.edits)?,
+                None => code.to_owned(),
+            };
+            let expected = expected.to_string() + "import './missing';\nimport 'react';\n";
+            assert_eq!(result, expected);
+        }
+
+        Ok(())
+    }
+}
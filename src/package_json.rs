@@ -0,0 +1,236 @@
+use anyhow::{anyhow, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::edit::TextEdit;
+use crate::jsonc;
+use crate::path;
+use crate::plan::{FileEdit, MovePlan};
+use crate::resolver::{PathAlias, Pattern};
+use crate::workspace;
+
+// Fields whose values, including nested conditions and arrays, are paths
+// relative to the package folder.
+const PATH_FIELDS: [&str; 8] = [
+    "main",
+    "module",
+    "browser",
+    "types",
+    "typings",
+    "bin",
+    "exports",
+    "typesVersions",
+];
+
+// Subpath imports such as #db/* declared in the `imports` field of the
+// package.json in dir. They apply to files below dir only.
+pub struct SubpathImports {
+    pub dir: PathBuf,
+    pub aliases: Vec<PathAlias>,
+}
+
+// Reads the subpath imports of a package.json. Targets in other packages,
+// such as "#dep": "some-package", are skipped.
+pub fn read_subpath_imports(file: &Path) -> Result<SubpathImports> {
+    let contents = fs::read_to_string(file).map_err(|_| anyhow!("Failed to read {:?}", file))?;
+    let dir = path::get_parent(file);
+    let mut targets: Vec<(String, Vec<Pattern>)> = vec![];
+
+    for string in jsonc::strings(&contents).unwrap_or_default() {
+        let key = match string.keys.as_slice() {
+            [field, key, ..]
+                if field.eq("imports")
+                    && key.starts_with('#')
+                    && string.value.starts_with("./") =>
+            {
+                key
+            }
+            _ => continue,
+        };
+        let target = path::join(&dir, Path::new(&string.value))?;
+        let target = Pattern::new(path::to_str(&target)?);
+
+        match targets.iter_mut().find(|(alias, _)| alias.eq(key)) {
+            Some((_, patterns)) => patterns.push(target),
+            None => targets.push((key.clone(), vec![target])),
+        }
+    }
+
+    let aliases = targets
+        .into_iter()
+        .map(|(alias, targets)| PathAlias {
+            alias: Pattern::new(&alias),
+            targets,
+        })
+        .collect();
+
+    Ok(SubpathImports { dir, aliases })
+}
+
+// Plans rewriting the path fields of every package.json under root that
+// point to files or folders moved by the plan. Only the changed strings are
+// replaced, so formatting is kept.
+pub fn plan_package_json(root: &Path, plan: &MovePlan) -> Result<Vec<FileEdit>> {
+    let mut file_edits = vec![];
+
+    for file in workspace::iter_package_json_files(root) {
+        let contents =
+            fs::read_to_string(&file).map_err(|_| anyhow!("Failed to read {:?}", file))?;
+        let strings = match jsonc::strings(&contents) {
+            Ok(strings) => strings,
+            Err(_) => continue,
+        };
+        let dir = path::get_parent(&file);
+        let new_dir = plan.new_location(&dir);
+
+        let edits: Vec<TextEdit> = strings
+            .into_iter()
+            .filter(|string| {
+                string
+                    .keys
+                    .first()
+                    .is_some_and(|key| PATH_FIELDS.contains(&key.as_str()))
+            })
+            .filter_map(|string| {
+                let new_text = plan.rewrite_path(&dir, &new_dir, &string.value)?;
+                Some(TextEdit {
+                    start: string.start,
+                    end: string.end,
+                    old_text: contents[string.start..string.end].to_owned(),
+                    new_text,
+                })
+            })
+            .collect();
+
+        if !edits.is_empty() {
+            file_edits.push(FileEdit { path: file, edits });
+        }
+    }
+
+    Ok(file_edits)
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use std::fs;
+
+    use crate::plan::{self, Rename};
+    use crate::resolver::Resolver;
+    use crate::test_dir::TempDir;
+
+    #[test]
+    fn it_updates_package_json_paths() -> Result<()> {
+        let tmp = TempDir::new("package-json");
+        fs::create_dir_all(tmp.join("src/cli"))?;
+        fs::create_dir_all(tmp.join("src/components"))?;
+        fs::write(tmp.join("src/index.ts"), "")?;
+        fs::write(tmp.join("src/cli/run.ts"), "")?;
+        fs::write(tmp.join("src/components/Button.ts"), "")?;
+        let package_json = r#"{
+  "name": "acme",
+  "main": "src/index.ts",
+  "types": "./src/index.ts",
+  "bin": { "acme": "./src/cli/run.ts" },
+  "exports": {
+    ".": { "types": "./src/index.ts", "default": ["./src/index.ts"] },
+    "./components/*": "./src/components/*.ts",
+    "./package.json": "./package.json"
+  },
+  "typesVersions": { "*": { "*": ["src/components/*"] } },
+  "description": "src/index.ts"
+}
+"#;
+        fs::write(tmp.join("package.json"), package_json)?;
+
+        let renames = vec![
+            Rename {
+                from: tmp.join("src/index.ts"),
+                to: tmp.join("src/main.ts"),
+            },
+            Rename {
+                from: tmp.join("src/cli"),
+                to: tmp.join("bin"),
+            },
+            Rename {
+                from: tmp.join("src/components"),
+                to: tmp.join("src/ui"),
+            },
+        ];
+        plan::plan_moves(&tmp, renames, &Resolver::default())?.apply()?;
+
+        assert_eq!(
+            fs::read_to_string(tmp.join("package.json"))?,
+            r#"{
+  "name": "acme",
+  "main": "src/main.ts",
+  "types": "./src/main.ts",
+  "bin": { "acme": "./bin/run.ts" },
+  "exports": {
+    ".": { "types": "./src/main.ts", "default": ["./src/main.ts"] },
+    "./components/*": "./src/ui/*.ts",
+    "./package.json": "./package.json"
+  },
+  "typesVersions": { "*": { "*": ["src/ui/*"] } },
+  "description": "src/index.ts"
+}
+"#
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_rewrites_subpath_imports() -> Result<()> {
+        let tmp = TempDir::new("subpath-imports");
+        fs::create_dir_all(tmp.join("src/app"))?;
+        fs::create_dir_all(tmp.join("src/db"))?;
+        fs::create_dir_all(tmp.join("tools"))?;
+        fs::write(
+            tmp.join("package.json"),
+            r##"{
+  "imports": {
+    "#db/*": "./src/db/*.js",
+    "#config": { "import": "./src/config.js" },
+    "#dep": "some-package"
+  }
+}"##,
+        )?;
+        fs::write(tmp.join("tools/package.json"), "{}")?;
+        fs::write(tmp.join("src/db/client.ts"), "")?;
+        fs::write(tmp.join("src/config.ts"), "")?;
+        let code = "import '#db/client';\nimport '#config';\nimport '#dep';\n";
+        fs::write(tmp.join("src/app/main.ts"), code)?;
+        fs::write(tmp.join("src/app/cli.ts"), code)?;
+
+        let mut resolver = Resolver::default();
+        resolver.add_subpath_imports(&tmp)?;
+
+        let renames = vec![
+            Rename {
+                from: tmp.join("src/db/client.ts"),
+                to: tmp.join("src/db/pg/client.ts"),
+            },
+            Rename {
+                from: tmp.join("src/config.ts"),
+                to: tmp.join("src/settings/config.ts"),
+            },
+            Rename {
+                from: tmp.join("src/app/cli.ts"),
+                to: tmp.join("tools/cli.ts"),
+            },
+        ];
+        plan::plan_moves(&tmp, renames, &resolver)?.apply()?;
+
+        assert_eq!(
+            fs::read_to_string(tmp.join("src/app/main.ts"))?,
+            "import '#db/pg/client';\nimport '../settings/config.js';\nimport '#dep';\n"
+        );
+        assert_eq!(
+            fs::read_to_string(tmp.join("tools/cli.ts"))?,
+            "import '../src/db/pg/client.js';\nimport '../src/settings/config.js';\nimport '#dep';\n"
+        );
+
+        Ok(())
+    }
+}
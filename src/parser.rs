@@ -1,41 +1,64 @@
 use anyhow::{anyhow, Result};
-use ropey::Rope;
 use tree_sitter::{Language, Parser, Query, QueryCursor, Tree};
 use tree_sitter_typescript::{language_tsx, language_typescript};
 
-const QUERY: &str = "(import_statement (string) @import)(export_statement (string) @import)";
+// Shared by the TypeScript and JavaScript grammars.
+const QUERY: &str = r#"
+(import_statement (string) @import)
+(export_statement (string) @import)
+(call_expression
+  function: (identifier) @function
+  arguments: (arguments . (string) @import .)
+  (#eq? @function "require"))
+(call_expression
+  function: (import)
+  arguments: (arguments . (string) @import .))
+"#;
+
+// tree-sitter-typescript 0.19 parses `export import x = require('./y')` as an
+// `import_alias` ending in `require`, followed by a parenthesized string.
+const TYPESCRIPT_QUERY: &str = r#"
+(import_require_clause (string) @import)
+((export_statement
+  declaration: (import_alias (identifier) @require .))
+ .
+ (expression_statement (parenthesized_expression . (string) @import .))
+ (#eq? @require "require"))
+"#;
 
 pub enum Lang {
     TypeScript,
     TypeScriptTsx,
+    JavaScript,
 }
 
 fn to_language(language: &Lang) -> Language {
     match language {
         Lang::TypeScript => language_typescript(),
         Lang::TypeScriptTsx => language_tsx(),
+        Lang::JavaScript => tree_sitter_javascript::language(),
     }
 }
 
-pub struct TextSlice {
-    start_row: usize,
-    start_col: usize,
-    end_row: usize,
-    end_col: usize,
+fn to_query(language: &Lang) -> String {
+    match language {
+        Lang::JavaScript => QUERY.to_owned(),
+        _ => QUERY.to_owned() + TYPESCRIPT_QUERY,
+    }
 }
 
-impl TextSlice {
-    pub fn to_index_range(&self, rope: &Rope) -> (usize, usize) {
-        let start_idx = rope.line_to_char(self.start_row) + self.start_col;
-        let end_idx = rope.line_to_char(self.end_row) + self.end_col;
-        (start_idx, end_idx)
-    }
+// Byte range of an import string, without the quotes.
+pub struct TextSlice {
+    pub start: usize,
+    pub end: usize,
 }
 
 pub struct ImportFinder {
+    source_code: String,
     tree: Tree,
     query: Query,
     cursor: QueryCursor,
+    import_index: u32,
 }
 
 impl ImportFinder {
@@ -43,32 +66,38 @@ impl ImportFinder {
         let language = to_language(&lang);
 
         let tree = parse_treesitter_tree(source_code, language)?;
-        let query = Query::new(language, &QUERY).unwrap();
+        let query = Query::new(language, &to_query(&lang)).unwrap();
         let cursor = QueryCursor::new();
+        let import_index = query
+            .capture_names()
+            .iter()
+            .position(|name| name == "import")
+            .ok_or_else(|| anyhow!("Query is missing @import capture"))?
+            as u32;
 
         Ok(Self {
+            source_code: source_code.to_string(),
             tree,
             query,
             cursor,
+            import_index,
         })
     }
 
     pub fn find_imports(&mut self) -> impl Iterator<Item = TextSlice> + '_ {
+        let source_code = self.source_code.as_bytes();
+        let import_index = self.import_index;
+
         self.cursor
-            .matches(&self.query, self.tree.root_node(), |_| "")
-            .into_iter()
+            .matches(&self.query, self.tree.root_node(), move |node| {
+                &source_code[node.byte_range()]
+            })
             .flat_map(|qm| qm.captures.iter())
+            .filter(move |query_capture| query_capture.index == import_index)
             .map(|query_capture| query_capture.node)
-            .map(|node| {
-                let start_point = node.start_position();
-                let end_point = node.end_position();
-
-                TextSlice {
-                    start_row: start_point.row,
-                    start_col: start_point.column + 1,
-                    end_row: end_point.row,
-                    end_col: end_point.column - 1,
-                }
+            .map(|node| TextSlice {
+                start: node.start_byte() + 1,
+                end: node.end_byte() - 1,
             })
     }
 }
@@ -82,35 +111,3 @@ fn parse_treesitter_tree(source_code: &str, language: Language) -> Result<Tree>
         .parse(source_code, None)
         .ok_or_else(|| anyhow!("Failed to parse"))
 }
-
-// (call_expression
-//   (identifier) @constant
-//   (#match? @constant "require")
-//   (arguments (string) @lol)
-// (call_expression
-//   (identifier) @constant
-//   (#match? @constant "require")
-//   (arguments (string) @lol)
-// )
-// )
-//
-//
-//
-// (call_expression
-//   (import)
-//   (arguments (string) @lol)
-// )
-// (import_statement (string) @lol)
-// (call_expression
-//    (identifier) @constant
-//    (#match? @constant "require")
-//    (arguments (string) @lol)
-// )
-//
-//
-//
-// (call_expression
-// (identifier) @function_name
-// (match? @function_name "require")
-// (arguments (string) @import_string))
-//
@@ -1,20 +1,16 @@
 use anyhow::{anyhow, Result};
 use pathdiff::diff_paths;
-use std::path::{Component, PathBuf};
+use std::path::{Component, Path, PathBuf};
 use std::vec;
 
-pub fn normalize(path: &PathBuf) -> Result<PathBuf> {
+pub fn normalize(path: &Path) -> Result<PathBuf> {
     let mut skip = 0;
     let mut components = vec![];
 
-    for component in path.components().into_iter().rev() {
+    for component in path.components().rev() {
         match component {
-            Component::Normal(_) => {
-                if skip > 0 {
-                    skip -= 1;
-                } else {
-                    components.push(component);
-                }
+            Component::Normal(_) if skip > 0 => {
+                skip -= 1;
             }
             Component::CurDir => (),
             Component::ParentDir => {
@@ -31,7 +27,7 @@ pub fn normalize(path: &PathBuf) -> Result<PathBuf> {
     }
 }
 
-pub fn diff(from_path: &PathBuf, to_path: &PathBuf) -> Result<PathBuf> {
+pub fn diff(from_path: &Path, to_path: &Path) -> Result<PathBuf> {
     let normalized_from_path = normalize(from_path)?;
     let normalized_to_path = normalize(to_path)?;
 
@@ -44,17 +40,21 @@ pub fn diff(from_path: &PathBuf, to_path: &PathBuf) -> Result<PathBuf> {
     })
 }
 
-pub fn get_parent(path: &PathBuf) -> PathBuf {
-    let mut path = path.clone();
+pub fn get_parent(path: &Path) -> PathBuf {
+    let mut path = path.to_path_buf();
     path.pop();
     path
 }
 
-pub fn join(dir: &PathBuf, path: &PathBuf) -> Result<PathBuf> {
+pub fn join(dir: &Path, path: &Path) -> Result<PathBuf> {
     let full_path = dir.join(path);
     normalize(&full_path)
 }
 
+pub fn to_str(path: &Path) -> Result<&str> {
+    path.to_str().ok_or_else(|| anyhow!("Non utf-8 path"))
+}
+
 #[cfg(test)]
 mod tests {
     use std::path::PathBuf;
@@ -0,0 +1,613 @@
+use anyhow::{anyhow, Result};
+use rayon::iter::{IntoParallelIterator, IntoParallelRefIterator, ParallelIterator};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::edit::{self, TextEdit};
+use crate::grep;
+use crate::import_string;
+use crate::journal::Journal;
+use crate::package_json;
+use crate::path;
+use crate::resolver::Resolver;
+use crate::transaction::Transaction;
+use crate::tsconfig;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Rename {
+    pub from: PathBuf,
+    pub to: PathBuf,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct FileEdit {
+    pub path: PathBuf,
+    pub edits: Vec<TextEdit>,
+}
+
+// Edits refer to files by their location before the renames.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct MovePlan {
+    pub renames: Vec<Rename>,
+    pub edits: Vec<FileEdit>,
+}
+
+fn to_tmp_path(path: &Path, idx: usize) -> Result<PathBuf> {
+    let file_name = path
+        .file_name()
+        .and_then(|file_name| file_name.to_str())
+        .ok_or_else(|| anyhow!("Invalid file name {:?}", path))?;
+    Ok(path.with_file_name(format!(".{}.mvts-{}", file_name, idx)))
+}
+
+fn to_file_edit(path: PathBuf, edits: Vec<TextEdit>) -> Option<FileEdit> {
+    match edits.is_empty() {
+        true => None,
+        false => Some(FileEdit { path, edits }),
+    }
+}
+
+impl MovePlan {
+    pub fn load(file: &Path) -> Result<Self> {
+        let contents =
+            fs::read_to_string(file).map_err(|_| anyhow!("Failed to read {:?}", file))?;
+        serde_json::from_str(&contents).map_err(|err| anyhow!("Invalid plan {:?}: {}", file, err))
+    }
+
+    pub fn save(&self, file: &Path) -> Result<()> {
+        let contents = serde_json::to_string_pretty(self)?;
+        fs::write(file, contents + "\n").map_err(|_| anyhow!("Failed to write {:?}", file))
+    }
+
+    // Location of path once the renames have been applied.
+    pub fn new_location(&self, path: &Path) -> PathBuf {
+        self.renames
+            .iter()
+            .find_map(|Rename { from, to }| match path.strip_prefix(from) {
+                Ok(rest) if rest.as_os_str().is_empty() => Some(to.clone()),
+                Ok(rest) => Some(to.join(rest)),
+                Err(_) => None,
+            })
+            .unwrap_or_else(|| path.to_path_buf())
+    }
+
+    // Rewrites a path written in a config file in dir, which moves to
+    // new_dir. Values with a glob pattern, such as "./src/*.js", are rewritten
+    // when the folder before the pattern moves.
+    pub fn rewrite_path(&self, dir: &Path, new_dir: &Path, value: &str) -> Option<String> {
+        let (path_part, pattern) = match value.find(&grep::GLOB_CHARS[..]) {
+            Some(idx) => value.split_at(idx),
+            None => (value, ""),
+        };
+        if path_part.is_empty()
+            || path_part.starts_with('/')
+            || path_part.contains(':')
+            || (!pattern.is_empty() && !path_part.ends_with('/'))
+        {
+            return None;
+        }
+
+        let old_path = path::join(dir, Path::new(path_part)).ok()?;
+        let new_path = self.new_location(&old_path);
+        let rel_path = path::diff(new_dir, &new_path).ok()?;
+        let rel_path = rel_path.to_str()?.replace('\\', "/");
+        if rel_path.is_empty() {
+            return None;
+        }
+
+        let mut new_value = match value.starts_with("./") && !rel_path.starts_with("..") {
+            true => format!("./{}", rel_path),
+            false => rel_path,
+        };
+        if path_part.ends_with('/') {
+            new_value.push('/');
+        }
+        new_value.push_str(pattern);
+
+        Some(new_value).filter(|new_value| new_value.ne(value))
+    }
+
+    // Every edit is computed before anything is written, and if writing or
+    // renaming fails halfway all changes made so far are rolled back.
+    pub fn apply(&self) -> Result<Journal> {
+        let contents: Vec<(&PathBuf, String, String)> = self
+            .edits
+            .par_iter()
+            .map(
+                |FileEdit { path, edits }| -> Result<(&PathBuf, String, String)> {
+                    let source_code = fs::read_to_string(path)
+                        .map_err(|_| anyhow!("Failed to read {:?}", path))?;
+                    let new_source_code = edit::apply_edits(&source_code, edits)
+                        .map_err(|err| anyhow!("{:?} has changed since planning: {}", path, err))?;
+                    Ok((path, source_code, new_source_code))
+                },
+            )
+            .collect::<Result<_>>()?;
+
+        let mut transaction = Transaction::default();
+
+        let result = contents
+            .into_iter()
+            .try_for_each(|(path, source_code, new_source_code)| {
+                transaction.write(path, source_code, new_source_code)
+            })
+            .and_then(|_| {
+                // Renaming through temporary names first makes swaps and
+                // cycles such as a -> b, b -> a work.
+                let tmp_paths = self
+                    .renames
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, Rename { from, .. })| to_tmp_path(from, idx))
+                    .collect::<Result<Vec<PathBuf>>>()?;
+
+                self.renames
+                    .iter()
+                    .zip(&tmp_paths)
+                    .try_for_each(|(Rename { from, .. }, tmp)| transaction.rename(from, tmp))?;
+
+                self.renames
+                    .iter()
+                    .zip(&tmp_paths)
+                    .try_for_each(|(Rename { to, .. }, tmp)| transaction.rename(tmp, to))
+            });
+
+        match result {
+            Ok(_) => Ok(transaction.into_journal()),
+            Err(err) => match transaction.rollback() {
+                Ok(_) => Err(err.context("All changes were rolled back")),
+                Err(rollback_err) => Err(err.context(rollback_err)),
+            },
+        }
+    }
+}
+
+fn into_dir(from: &Path, target: &Path) -> Result<PathBuf> {
+    let file_name = from
+        .file_name()
+        .ok_or_else(|| anyhow!("Invalid source {:?}", from))?;
+    Ok(target.join(file_name))
+}
+
+// Resolves targets like mv does: with several sources, or when the target is
+// an existing folder, every source is moved into the target folder. Files
+// matching glob sources keep their path relative to the glob unless flatten
+// is set, and the target folder is created when needed.
+pub fn to_renames(sources: Vec<PathBuf>, target: PathBuf, flatten: bool) -> Result<Vec<Rename>> {
+    let has_glob = sources.iter().any(|source| grep::is_glob(source));
+    let is_dir_target = sources.len() > 1 || has_glob || target.is_dir();
+
+    if is_dir_target && !target.is_dir() && (target.exists() || !has_glob) {
+        return Err(anyhow!("Target {:?} is not a directory", target));
+    }
+
+    let mut renames = vec![];
+
+    for from in sources {
+        if grep::is_glob(&from) {
+            let (base, files) = grep::glob_files(&from)?;
+
+            if files.is_empty() {
+                return Err(anyhow!("No files match {:?}", from));
+            }
+
+            for file in files {
+                let to = match flatten {
+                    true => into_dir(&file, &target)?,
+                    false => path::join(&target, &path::diff(&base, &file)?)?,
+                };
+                renames.push(Rename { from: file, to });
+            }
+            continue;
+        }
+
+        if !from.exists() {
+            return Err(anyhow!("{:?} does not exist", from));
+        }
+        let to = match is_dir_target {
+            true => into_dir(&from, &target)?,
+            false => target.clone(),
+        };
+        renames.push(Rename { from, to });
+    }
+
+    check_renames(&renames)?;
+
+    Ok(renames)
+}
+
+// Takes pairs from a manifest literally, without mv style resolution of
+// targets, so that every file ends up exactly where the manifest says.
+pub fn to_manifest_renames(pairs: Vec<(PathBuf, PathBuf)>) -> Result<Vec<Rename>> {
+    if pairs.is_empty() {
+        return Err(anyhow!("Manifest does not contain any moves"));
+    }
+
+    let renames: Vec<Rename> = pairs
+        .into_iter()
+        .map(|(from, to)| match from.exists() {
+            true => Ok(Rename { from, to }),
+            false => Err(anyhow!("{:?} does not exist", from)),
+        })
+        .collect::<Result<_>>()?;
+
+    check_renames(&renames)?;
+
+    Ok(renames)
+}
+
+// Rejects renames that would move the same file twice or move two files to
+// the same place. A target may be the source of another rename, for swaps.
+pub fn check_renames(renames: &[Rename]) -> Result<()> {
+    for (idx, rename) in renames.iter().enumerate() {
+        let overlapping = renames[..idx].iter().find(|other| {
+            other.to.eq(&rename.to)
+                || other.from.starts_with(&rename.from)
+                || rename.from.starts_with(&other.from)
+        });
+        if let Some(other) = overlapping {
+            return Err(anyhow!(
+                "Cannot move both {:?} to {:?} and {:?} to {:?}",
+                other.from,
+                other.to,
+                rename.from,
+                rename.to
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+// Expands folder renames into the renames of the files inside them. Assets
+// are included, so that imports of them get updated too.
+pub(crate) fn to_moved_files(renames: &[Rename]) -> Result<Vec<(PathBuf, PathBuf)>> {
+    let mut moved_files = vec![];
+
+    for Rename { from, to } in renames {
+        if !from.is_dir() {
+            moved_files.push((from.clone(), to.clone()));
+            continue;
+        }
+
+        for file in grep::iter_moved_files(from) {
+            let rel_path = path::diff(from, &file)?;
+            let new_file = path::join(to, &rel_path)?;
+            moved_files.push((file, new_file));
+        }
+    }
+
+    Ok(moved_files)
+}
+
+// Plans all renames together, so that files importing several of the moved
+// files, including the moved files themselves, are rewritten in one pass.
+pub fn plan_moves(root: &Path, renames: Vec<Rename>, resolver: &Resolver) -> Result<MovePlan> {
+    let moved_files = &to_moved_files(&renames)?;
+
+    let moved_edits: Vec<Option<FileEdit>> = moved_files
+        .into_par_iter()
+        .filter(|(source_file, _)| grep::is_source_file(source_file))
+        .map(|(source_file, target_file)| -> Result<Option<FileEdit>> {
+            let source_code = fs::read_to_string(source_file)
+                .map_err(|_| anyhow!("Failed to read {:?}", source_file))?;
+
+            let edits = edit::move_source_file(
+                &source_code,
+                source_file,
+                target_file,
+                moved_files,
+                resolver,
+            )?;
+
+            Ok(to_file_edit(source_file.clone(), edits))
+        })
+        .collect::<Result<_>>()?;
+
+    let aliases: Vec<Vec<String>> = moved_files
+        .iter()
+        .map(|(moved_file, _)| resolver.aliases_to(moved_file))
+        .collect();
+
+    let other_files: Vec<PathBuf> = grep::iter_files(root)
+        .filter(|path| {
+            !moved_files
+                .iter()
+                .any(|(moved_path, _)| moved_path.eq(path))
+        })
+        .collect();
+
+    let other_edits: Vec<Option<FileEdit>> = other_files
+        .into_par_iter()
+        .map(|source_file| -> Result<Option<FileEdit>> {
+            let source_code = fs::read_to_string(&source_file)
+                .map_err(|_| anyhow!("Failed to read {:?}", source_file))?;
+
+            let contains_import =
+                moved_files
+                    .iter()
+                    .zip(&aliases)
+                    .any(|((moved_file, _), aliases)| {
+                        let import_string =
+                            import_string::from_paths(&source_file, moved_file).unwrap_or_default();
+                        // NodeNext imports of ./util.mts name ./util.mjs instead.
+                        let emitted_import_string = import_string::to_emitted_file(moved_file)
+                            .and_then(|emitted_file| {
+                                import_string::from_paths(&source_file, &emitted_file).ok()
+                            });
+                        source_code.contains(import_string::to_node_import(&import_string))
+                            || emitted_import_string
+                                .map(|emitted| source_code.contains(&emitted))
+                                .unwrap_or(false)
+                            || aliases.iter().any(|alias| source_code.contains(alias))
+                    });
+
+            if !contains_import {
+                return Ok(None);
+            }
+
+            let edits =
+                edit::move_required_file(&source_code, &source_file, moved_files, resolver)?;
+
+            Ok(to_file_edit(source_file, edits))
+        })
+        .collect::<Result<_>>()?;
+
+    let edits = moved_edits
+        .into_iter()
+        .chain(other_edits)
+        .flatten()
+        .collect();
+
+    let mut plan = MovePlan { renames, edits };
+    let package_json_edits = package_json::plan_package_json(root, &plan)?;
+    plan.edits.extend(package_json_edits);
+    let tsconfig_edits = tsconfig::plan_tsconfig_files(root, &plan)?;
+    plan.edits.extend(tsconfig_edits);
+
+    Ok(plan)
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use std::fs;
+    use std::path::PathBuf;
+
+    use super::{FileEdit, MovePlan, Rename};
+    use crate::edit::TextEdit;
+    use crate::test_dir::TempDir;
+
+    #[test]
+    fn it_round_trips_through_json() -> Result<()> {
+        let plan = MovePlan {
+            renames: vec![Rename {
+                from: "/p/src/a.ts".into(),
+                to: "/p/src/b/a.ts".into(),
+            }],
+            edits: vec![FileEdit {
+                path: "/p/src/main.ts".into(),
+                edits: vec![TextEdit {
+                    start: 15,
+                    end: 18,
+                    old_text: "./a".into(),
+                    new_text: "./b/a".into(),
+                }],
+            }],
+        };
+
+        let json = serde_json::to_string(&plan)?;
+        let result: MovePlan = serde_json::from_str(&json)?;
+        assert_eq!(result, plan);
+        Ok(())
+    }
+
+    #[test]
+    fn it_resolves_targets_like_mv() -> Result<()> {
+        let tmp = TempDir::new("renames");
+        fs::create_dir_all(tmp.join("utils"))?;
+        fs::create_dir_all(tmp.join("lib"))?;
+        fs::write(tmp.join("a.ts"), "")?;
+        fs::write(tmp.join("b.tsx"), "")?;
+
+        let renames = super::to_renames(vec![tmp.join("a.ts")], tmp.join("c.ts"), false)?;
+        assert_eq!(renames[0].to, tmp.join("c.ts"));
+
+        let renames = super::to_renames(vec![tmp.join("a.ts")], tmp.join("lib"), false)?;
+        assert_eq!(renames[0].to, tmp.join("lib/a.ts"));
+
+        let sources = vec![tmp.join("a.ts"), tmp.join("b.tsx"), tmp.join("utils")];
+        let renames = super::to_renames(sources, tmp.join("lib"), false)?;
+        let targets: Vec<PathBuf> = renames.into_iter().map(|rename| rename.to).collect();
+        assert_eq!(
+            targets,
+            vec![
+                tmp.join("lib/a.ts"),
+                tmp.join("lib/b.tsx"),
+                tmp.join("lib/utils")
+            ]
+        );
+
+        let sources = vec![tmp.join("a.ts"), tmp.join("b.tsx")];
+        assert!(super::to_renames(sources, tmp.join("c.ts"), false).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_swaps_files() -> Result<()> {
+        let tmp = TempDir::new("swap");
+        fs::write(tmp.join("a.ts"), "a")?;
+        fs::write(tmp.join("b.ts"), "b")?;
+
+        let pairs = vec![
+            (tmp.join("a.ts"), tmp.join("b.ts")),
+            (tmp.join("b.ts"), tmp.join("a.ts")),
+        ];
+        let renames = super::to_manifest_renames(pairs)?;
+        MovePlan {
+            renames,
+            edits: vec![],
+        }
+        .apply()?;
+
+        assert_eq!(fs::read_to_string(tmp.join("a.ts"))?, "b");
+        assert_eq!(fs::read_to_string(tmp.join("b.ts"))?, "a");
+
+        let pairs = vec![
+            (tmp.join("a.ts"), tmp.join("c.ts")),
+            (tmp.join("b.ts"), tmp.join("c.ts")),
+        ];
+        assert!(super::to_manifest_renames(pairs).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_moves_assets() -> Result<()> {
+        let tmp = TempDir::new("asset");
+        fs::create_dir_all(tmp.join("src"))?;
+        fs::write(
+            tmp.join("src/logo.png"),
+            [0x89, 0x50, 0x4e, 0x47, 0xff, 0xfe],
+        )?;
+        fs::write(
+            tmp.join("src/App.tsx"),
+            "import logo from './logo.png';\nimport './logo';\n",
+        )?;
+
+        let renames = vec![Rename {
+            from: tmp.join("src/logo.png"),
+            to: tmp.join("assets/images/logo.png"),
+        }];
+        let plan = super::plan_moves(&tmp, renames, &Default::default())?;
+        plan.apply()?;
+
+        assert!(tmp.join("assets/images/logo.png").exists());
+        assert_eq!(
+            fs::read_to_string(tmp.join("src/App.tsx"))?,
+            "import logo from '../assets/images/logo.png';\nimport './logo';\n"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_updates_imports_of_assets_in_moved_folders() -> Result<()> {
+        let tmp = TempDir::new("folder-asset");
+        fs::create_dir_all(tmp.join("src/icons"))?;
+        fs::write(tmp.join("src/icons/logo.svg"), "<svg/>")?;
+        fs::write(tmp.join("src/icons/theme.css"), ".logo {}")?;
+        fs::write(tmp.join("src/icons/Logo.tsx"), "import './theme.css';\n")?;
+        fs::write(
+            tmp.join("src/App.tsx"),
+            "import logo from './icons/logo.svg';\nimport { Logo } from './icons/Logo';\n",
+        )?;
+
+        let renames = vec![Rename {
+            from: tmp.join("src/icons"),
+            to: tmp.join("assets/icons"),
+        }];
+        super::plan_moves(&tmp, renames, &Default::default())?.apply()?;
+
+        assert_eq!(
+            fs::read_to_string(tmp.join("src/App.tsx"))?,
+            "import logo from '../assets/icons/logo.svg';\nimport { Logo } from '../assets/icons/Logo';\n"
+        );
+        assert_eq!(
+            fs::read_to_string(tmp.join("assets/icons/Logo.tsx"))?,
+            "import './theme.css';\n"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_updates_imports_of_emitted_module_files() -> Result<()> {
+        let tmp = TempDir::new("emitted");
+        fs::create_dir_all(tmp.join("src"))?;
+        fs::write(tmp.join("src/util.mts"), "")?;
+        fs::write(tmp.join("src/conf.cts"), "")?;
+        fs::write(
+            tmp.join("src/main.mts"),
+            "import { util } from './util.mjs';\nimport conf = require('./conf.cjs');\n",
+        )?;
+
+        let renames = vec![
+            Rename {
+                from: tmp.join("src/util.mts"),
+                to: tmp.join("lib/util.mts"),
+            },
+            Rename {
+                from: tmp.join("src/conf.cts"),
+                to: tmp.join("lib/conf.cts"),
+            },
+        ];
+        super::plan_moves(&tmp, renames, &Default::default())?.apply()?;
+
+        assert_eq!(
+            fs::read_to_string(tmp.join("src/main.mts"))?,
+            "import { util } from '../lib/util.mjs';\nimport conf = require('../lib/conf.cjs');\n"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_moves_hidden_files_with_their_folder() -> Result<()> {
+        let tmp = TempDir::new("folder-hidden");
+        fs::create_dir_all(tmp.join("src/a"))?;
+        fs::write(tmp.join("src/a/.env"), "A=1")?;
+        fs::write(tmp.join("src/a/b.ts"), "import './.env';\n")?;
+
+        let renames = vec![Rename {
+            from: tmp.join("src/a"),
+            to: tmp.join("src/c"),
+        }];
+        let plan = super::plan_moves(&tmp, renames, &Default::default())?;
+        assert!(plan.edits.is_empty());
+        plan.apply()?;
+
+        assert_eq!(
+            fs::read_to_string(tmp.join("src/c/b.ts"))?,
+            "import './.env';\n"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_expands_glob_sources() -> Result<()> {
+        let tmp = TempDir::new("glob");
+        fs::create_dir_all(tmp.join("src/a/b"))?;
+        fs::write(tmp.join("src/a/A.stories.tsx"), "")?;
+        fs::write(tmp.join("src/a/b/B.stories.tsx"), "")?;
+        fs::write(tmp.join("src/a/b/B.tsx"), "")?;
+
+        let pattern = tmp.join("src/**/*.stories.tsx");
+        let mut renames = super::to_renames(vec![pattern.clone()], tmp.join("stories"), false)?;
+        renames.sort_by(|a, b| a.from.cmp(&b.from));
+        let targets: Vec<PathBuf> = renames.into_iter().map(|rename| rename.to).collect();
+        assert_eq!(
+            targets,
+            vec![
+                tmp.join("stories/a/A.stories.tsx"),
+                tmp.join("stories/a/b/B.stories.tsx")
+            ]
+        );
+
+        let mut renames = super::to_renames(vec![pattern], tmp.join("stories"), true)?;
+        renames.sort_by(|a, b| a.from.cmp(&b.from));
+        let targets: Vec<PathBuf> = renames.into_iter().map(|rename| rename.to).collect();
+        assert_eq!(
+            targets,
+            vec![
+                tmp.join("stories/A.stories.tsx"),
+                tmp.join("stories/B.stories.tsx")
+            ]
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,253 @@
+use anyhow::{anyhow, Result};
+use regex::Regex;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::case::Case;
+use crate::grep;
+use crate::path;
+use crate::plan::Rename;
+
+// A sed style substitution such as s/Container(\.tsx?)$/View$1/.
+#[derive(Debug)]
+pub struct Substitution {
+    regex: Regex,
+    replacement: String,
+}
+
+// Splits on unescaped delimiters. Escaped delimiters lose their backslash,
+// other escapes are kept for the regex.
+fn split_escaped(text: &str, delimiter: char) -> Vec<String> {
+    let mut parts = vec![String::new()];
+    let mut chars = text.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(next) if next == delimiter => parts.last_mut().unwrap().push(next),
+                Some(next) => {
+                    parts.last_mut().unwrap().push(c);
+                    parts.last_mut().unwrap().push(next);
+                }
+                None => parts.last_mut().unwrap().push(c),
+            },
+            c if c == delimiter => parts.push(String::new()),
+            c => parts.last_mut().unwrap().push(c),
+        }
+    }
+
+    parts
+}
+
+impl Substitution {
+    pub fn parse(expression: &str) -> Result<Self> {
+        let invalid = || anyhow!("Expected s/regex/replacement/, got {:?}", expression);
+
+        let mut chars = expression.chars();
+        let delimiter = match (chars.next(), chars.next()) {
+            (Some('s'), Some(delimiter)) if !delimiter.is_alphanumeric() => delimiter,
+            _ => return Err(invalid()),
+        };
+
+        let parts = split_escaped(chars.as_str(), delimiter);
+        let (pattern, replacement) = match parts.as_slice() {
+            [pattern, replacement, flags] if flags.is_empty() => (pattern, replacement),
+            [pattern, replacement] => (pattern, replacement),
+            _ => return Err(invalid()),
+        };
+
+        let regex =
+            Regex::new(pattern).map_err(|err| anyhow!("Invalid regex {:?}: {}", pattern, err))?;
+
+        Ok(Substitution {
+            regex,
+            replacement: replacement.clone(),
+        })
+    }
+
+    // Returns None when the path does not match.
+    pub fn apply(&self, text: &str) -> Option<String> {
+        match self.regex.is_match(text) {
+            true => Some(
+                self.regex
+                    .replace(text, self.replacement.as_str())
+                    .into_owned(),
+            ),
+            false => None,
+        }
+    }
+}
+
+// Converts the file name of a relative path, leaving its folders alone.
+pub fn to_case(rel_path: &str, case: Case) -> Option<String> {
+    Some(match rel_path.rsplit_once('/') {
+        Some((dir, file_name)) => format!("{}/{}", dir, case.convert_file_name(file_name)),
+        None => case.convert_file_name(rel_path),
+    })
+}
+
+// Renames every source file under dir for which new_path returns a changed
+// path. new_path is given the path of the file relative to dir.
+pub fn to_renames<F>(dir: &Path, new_path: F) -> Result<Vec<Rename>>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let mut renames = vec![];
+
+    for file in grep::iter_files(dir) {
+        let rel_path = path::diff(dir, &file)?;
+        let rel_path_str = rel_path
+            .to_str()
+            .ok_or_else(|| anyhow!("Non utf-8 path {:?}", file))?;
+
+        let new_rel_path = match new_path(rel_path_str) {
+            Some(new_rel_path) if new_rel_path.ne(rel_path_str) => new_rel_path,
+            _ => continue,
+        };
+
+        if new_rel_path.is_empty() || new_rel_path.ends_with('/') {
+            return Err(anyhow!("{:?} would be renamed to {:?}", file, new_rel_path));
+        }
+
+        renames.push(Rename {
+            to: path::join(dir, Path::new(&new_rel_path))?,
+            from: file,
+        });
+    }
+
+    if renames.is_empty() {
+        return Err(anyhow!("No files in {:?} need renaming", dir));
+    }
+
+    check_collisions(&renames)?;
+
+    Ok(renames)
+}
+
+// On case insensitive file systems Button.tsx exists when button.tsx does, so
+// the exact file name is looked up from the folder instead.
+fn exists_with_case(path: &Path) -> bool {
+    let (dir, file_name) = match (path.parent(), path.file_name()) {
+        (Some(dir), Some(file_name)) => (dir, file_name),
+        _ => return path.exists(),
+    };
+
+    fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .any(|entry| entry.file_name().eq(file_name))
+        })
+        .unwrap_or(false)
+}
+
+// Reports every destination claimed by several files, or by a file that
+// already exists and is not itself renamed away.
+fn check_collisions(renames: &[Rename]) -> Result<()> {
+    let mut sources_by_target: HashMap<&PathBuf, Vec<&PathBuf>> = HashMap::new();
+    for Rename { from, to } in renames {
+        sources_by_target.entry(to).or_default().push(from);
+    }
+
+    let mut collisions: Vec<String> = sources_by_target
+        .into_iter()
+        .filter(|(target, sources)| {
+            sources.len() > 1
+                || (exists_with_case(target)
+                    && !renames.iter().any(|rename| rename.from.eq(*target)))
+        })
+        .map(|(target, sources)| {
+            let sources: Vec<String> = sources
+                .iter()
+                .map(|source| format!("{:?}", source))
+                .collect();
+            format!("{} -> {:?}", sources.join(", "), target)
+        })
+        .collect();
+
+    collisions.sort();
+
+    match collisions.is_empty() {
+        true => Ok(()),
+        false => Err(anyhow!(
+            "Renames would overwrite files:\n{}",
+            collisions.join("\n")
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use std::fs;
+
+    use super::Substitution;
+    use crate::case::Case;
+    use crate::test_dir::TempDir;
+
+    macro_rules! substitution_tests {
+        ($($name:ident: $value:expr,)*) => {
+        $(
+            #[test]
+            fn $name() {
+                let (expression, input, expected) = $value;
+                let substitution = Substitution::parse(expression).unwrap();
+                let result = substitution.apply(input);
+                assert_eq!(result.as_deref(), expected);
+            }
+        )*
+        }
+    }
+
+    substitution_tests! {
+        substitution_0: (r"s/Container(\.tsx?)$/View$1/", "a/UserContainer.tsx", Some("a/UserView.tsx")),
+        substitution_1: (r"s/Container(\.tsx?)$/View$1/", "a/Container/index.ts", None),
+        substitution_2: (r"s|^components/|ui/|", "components/Button.tsx", Some("ui/Button.tsx")),
+        substitution_3: (r"s/\//_/", "a/b.ts", Some("a_b.ts")),
+        substitution_4: (r"s/(\w+)\.test\.ts$/__tests__\/${1}.ts/", "a/b.test.ts", Some("a/__tests__/b.ts")),
+    }
+
+    #[test]
+    fn it_rejects_invalid_expressions() {
+        assert!(Substitution::parse("Container").is_err());
+        assert!(Substitution::parse("s/a/b/c/d").is_err());
+        assert!(Substitution::parse("s/(/b/").is_err());
+    }
+
+    #[test]
+    fn it_detects_collisions() -> Result<()> {
+        let tmp = TempDir::new("rename");
+        fs::write(tmp.join("UserContainer.tsx"), "")?;
+        fs::write(tmp.join("UserContainer.ts"), "")?;
+
+        let substitution = Substitution::parse(r"s/Container(\.tsx?)$/View$1/")?;
+        let mut renames = super::to_renames(&tmp, |path| substitution.apply(path))?;
+        renames.sort_by(|a, b| a.from.cmp(&b.from));
+        assert_eq!(renames[0].to, tmp.join("UserView.ts"));
+        assert_eq!(renames[1].to, tmp.join("UserView.tsx"));
+
+        let substitution = Substitution::parse(r"s/Container\.tsx?$/View.tsx/")?;
+        assert!(super::to_renames(&tmp, |path| substitution.apply(path)).is_err());
+
+        fs::write(tmp.join("UserView.ts"), "")?;
+        let substitution = Substitution::parse(r"s/Container(\.tsx?)$/View$1/")?;
+        assert!(super::to_renames(&tmp, |path| substitution.apply(path)).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_allows_case_only_renames() -> Result<()> {
+        let tmp = TempDir::new("rename-case");
+        fs::create_dir_all(tmp.join("Components"))?;
+        fs::write(tmp.join("Components/Button.tsx"), "")?;
+        fs::write(tmp.join("Components/index.ts"), "")?;
+
+        let renames = super::to_renames(&tmp, |path| super::to_case(path, Case::Kebab))?;
+        assert_eq!(renames.len(), 1);
+        assert_eq!(renames[0].to, tmp.join("Components/button.tsx"));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,633 @@
+use anyhow::{anyhow, Result};
+use std::path::{Path, PathBuf};
+
+use crate::import_string;
+use crate::normalize;
+use crate::package_json::{self, SubpathImports};
+use crate::path;
+use crate::tsconfig::{self, ConfigScope};
+use crate::workspace::{self, Package};
+
+pub struct Pattern {
+    prefix: String,
+    suffix: Option<String>,
+}
+
+impl Pattern {
+    pub fn new(pattern: &str) -> Self {
+        match pattern.find('*') {
+            Some(idx) => Self {
+                prefix: pattern[..idx].to_string(),
+                suffix: Some(pattern[idx + 1..].to_string()),
+            },
+            None => Self {
+                prefix: pattern.to_string(),
+                suffix: None,
+            },
+        }
+    }
+
+    fn capture<'a>(&self, string: &'a str) -> Option<&'a str> {
+        match &self.suffix {
+            Some(suffix) => string
+                .strip_prefix(&self.prefix)
+                .and_then(|rest| rest.strip_suffix(suffix.as_str())),
+            None if string.eq(&self.prefix) => Some(""),
+            None => None,
+        }
+    }
+
+    // Exact patterns may name the file itself, e.g. "config/index.ts".
+    fn capture_module<'a>(&self, module_path: &'a str, file: &str) -> Option<&'a str> {
+        match &self.suffix {
+            Some(_) => self.capture(module_path),
+            None if self.prefix.eq(module_path) || self.prefix.eq(file) => Some(""),
+            None => None,
+        }
+    }
+
+    fn fill(&self, capture: &str) -> String {
+        match &self.suffix {
+            Some(suffix) => format!("{}{}{}", self.prefix, capture, suffix),
+            None => self.prefix.clone(),
+        }
+    }
+}
+
+pub struct PathAlias {
+    pub alias: Pattern,
+    pub targets: Vec<Pattern>,
+}
+
+// Resolves import strings through tsconfig.json aliases, workspace package
+// names and package.json subpath imports.
+#[derive(Default)]
+pub struct Resolver {
+    scopes: Vec<ConfigScope>,
+    package_aliases: Vec<PathAlias>,
+    packages: Vec<Package>,
+    subpath_imports: Vec<SubpathImports>,
+}
+
+// Checks whether a module path (with or without a suffix, or pointing to a
+// folder with an index file) refers to required_file.
+fn points_to(module_path: &Path, required_file: &Path) -> bool {
+    let required = match required_file.to_str() {
+        Some(required) => required,
+        None => return false,
+    };
+    let wo_index = import_string::to_node_import(required);
+    let module_path = match path::normalize(module_path) {
+        Ok(module_path) => module_path,
+        Err(_) => return false,
+    };
+
+    module_path.eq(required_file)
+        || module_path.eq(&PathBuf::from(wo_index))
+        || module_path.eq(&PathBuf::from(wo_index.to_owned() + "/index"))
+        || points_to_emitted(&module_path, required_file)
+}
+
+fn points_to_emitted(module_path: &Path, required_file: &Path) -> bool {
+    import_string::to_emitted_file(required_file)
+        .map(|emitted_file| path::normalize(module_path).ok() == Some(emitted_file))
+        .unwrap_or(false)
+}
+
+fn resolve_aliases<'a>(
+    aliases: impl IntoIterator<Item = &'a PathAlias>,
+    import_string: &str,
+) -> Vec<PathBuf> {
+    aliases
+        .into_iter()
+        .filter_map(|PathAlias { alias, targets }| {
+            alias
+                .capture(import_string)
+                .map(|capture| targets.iter().map(move |target| target.fill(capture)))
+        })
+        .flatten()
+        .map(PathBuf::from)
+        .collect()
+}
+
+impl Resolver {
+    pub fn load(root: &Path) -> Result<Self> {
+        Ok(Self {
+            scopes: tsconfig::read_config_scopes(root)?,
+            ..Self::default()
+        })
+    }
+
+    #[cfg(test)]
+    pub fn parse(config_dir: &Path, contents: &str) -> Result<Self> {
+        Ok(Self {
+            scopes: vec![tsconfig::parse_config_scope(config_dir, contents)?],
+            ..Self::default()
+        })
+    }
+
+    // Workspace packages work like aliases from the package name to its
+    // folder, so deep imports such as @acme/ui/src/Button get rewritten. They
+    // are tried after paths but before the baseUrl catch-all.
+    pub fn add_packages(&mut self, packages: &[Package]) -> Result<()> {
+        let package_aliases = packages
+            .iter()
+            .map(|Package { name, dir }| -> Result<PathAlias> {
+                let target = dir.join("*");
+                Ok(PathAlias {
+                    alias: Pattern::new(&format!("{}/*", name)),
+                    targets: vec![Pattern::new(path::to_str(&target)?)],
+                })
+            })
+            .collect::<Result<Vec<PathAlias>>>()?;
+
+        self.package_aliases.extend(package_aliases);
+        self.packages.extend_from_slice(packages);
+        Ok(())
+    }
+
+    // Subpath imports resolve against the package.json closest to the
+    // importing file, so one entry is kept for every package.json under root.
+    pub fn add_subpath_imports(&mut self, root: &Path) -> Result<()> {
+        for file in workspace::iter_package_json_files(root) {
+            self.subpath_imports
+                .push(package_json::read_subpath_imports(&file)?);
+        }
+        Ok(())
+    }
+
+    fn subpath_scope(&self, source_file: &Path) -> Option<&SubpathImports> {
+        self.subpath_imports
+            .iter()
+            .filter(|scope| source_file.starts_with(&scope.dir))
+            .max_by_key(|scope| scope.dir.components().count())
+    }
+
+    fn is_same_subpath_scope(&self, file: &Path, other_file: &Path) -> bool {
+        self.subpath_scope(file).map(|scope| &scope.dir)
+            == self.subpath_scope(other_file).map(|scope| &scope.dir)
+    }
+
+    fn subpath_aliases(&self, source_file: &Path) -> &[PathAlias] {
+        self.subpath_scope(source_file)
+            .map(|scope| scope.aliases.as_slice())
+            .unwrap_or_default()
+    }
+
+    // Relative imports never cross a package.json boundary. Files in another
+    // workspace package are imported by package name instead.
+    pub fn to_package_import(&self, source_file: &Path, import_string: String) -> Result<String> {
+        if !import_string.starts_with('.') {
+            return Ok(import_string);
+        }
+
+        let required_path = import_string::to_path(source_file, &import_string)?;
+        let package = match workspace::package_of(&self.packages, &required_path) {
+            Some(package)
+                if workspace::package_of(&self.packages, source_file) != Some(package) =>
+            {
+                package
+            }
+            _ => return Ok(import_string),
+        };
+
+        let rel_path = required_path
+            .strip_prefix(&package.dir)
+            .map_err(|_| anyhow!("Failed to get path of {:?} in its package", required_path))?;
+
+        Ok(match rel_path.as_os_str().is_empty() {
+            true => package.name.clone(),
+            false => format!("{}/{}", package.name, path::to_str(rel_path)?),
+        })
+    }
+
+    // The reverse of to_package_import, for files moved into the package they
+    // import by name. Imports of the package itself resolve to its entry point
+    // and are left alone.
+    pub fn to_relative_import(
+        &self,
+        old_source_file: &Path,
+        source_file: &Path,
+        import_string: String,
+    ) -> Result<String> {
+        if !self.crosses_package(old_source_file, source_file) {
+            return Ok(import_string);
+        }
+
+        let package = match workspace::package_of(&self.packages, source_file) {
+            Some(package) if workspace::is_import_of(&import_string, package) => package,
+            _ => return Ok(import_string),
+        };
+
+        match import_string[package.name.len()..].strip_prefix('/') {
+            Some(rel_path) => import_string::from_paths(source_file, &package.dir.join(rel_path)),
+            None => Ok(import_string),
+        }
+    }
+
+    // Files that stay in their package keep the import style they had.
+    fn crosses_package(&self, old_location: &Path, new_location: &Path) -> bool {
+        workspace::package_of(&self.packages, old_location)
+            != workspace::package_of(&self.packages, new_location)
+    }
+
+    fn is_own_package_import(&self, source_file: &Path, import_string: &str) -> bool {
+        workspace::package_of(&self.packages, source_file)
+            .map(|package| workspace::is_import_of(import_string, package))
+            .unwrap_or(false)
+    }
+
+    fn config_scope(&self, source_file: &Path) -> Option<&ConfigScope> {
+        self.scopes
+            .iter()
+            .filter(|scope| source_file.starts_with(&scope.dir))
+            .max_by_key(|scope| scope.dir.components().count())
+    }
+
+    // Aliases available to source_file in the order they are tried.
+    fn aliases(&self, source_file: &Path) -> Vec<&PathAlias> {
+        let scope = self.config_scope(source_file);
+
+        scope
+            .into_iter()
+            .flat_map(|scope| scope.aliases.iter())
+            .chain(self.package_aliases.iter())
+            .chain(scope.and_then(|scope| scope.catch_all.as_ref()))
+            .collect()
+    }
+
+    fn resolve(&self, source_file: &Path, import_string: &str) -> Vec<PathBuf> {
+        resolve_aliases(self.aliases(source_file), import_string)
+    }
+
+    // All aliased import strings (without suffix) that point to file.
+    pub fn aliases_to(&self, file: &Path) -> Vec<String> {
+        let file = match file.to_str() {
+            Some(file) => file,
+            None => return vec![],
+        };
+        let module_path = import_string::to_node_import(file);
+        // Subpath imports often name the emitted file, e.g. "./src/*.js".
+        let emitted_file = import_string::to_emitted_file(Path::new(file));
+        let emitted_file = emitted_file.as_ref().and_then(|file| file.to_str());
+
+        let config_aliases = self
+            .scopes
+            .iter()
+            .flat_map(|scope| scope.aliases.iter().chain(scope.catch_all.as_ref()));
+        let subpath_aliases = self
+            .subpath_imports
+            .iter()
+            .flat_map(|scope| scope.aliases.iter());
+
+        config_aliases
+            .chain(self.package_aliases.iter())
+            .chain(subpath_aliases)
+            .flat_map(|PathAlias { alias, targets }| {
+                targets.iter().filter_map(move |target| {
+                    target
+                        .capture_module(module_path, file)
+                        .or_else(|| emitted_file.and_then(|file| target.capture(file)))
+                        .map(|capture| alias.fill(capture))
+                })
+            })
+            .collect()
+    }
+
+    fn to_alias(
+        &self,
+        source_file: &Path,
+        import_string: &str,
+        new_location: &Path,
+    ) -> Option<String> {
+        let has_suffix = PathBuf::from(import_string).extension().is_some();
+        let file = new_location.to_str()?;
+        let module_path = match has_suffix {
+            true => file,
+            false => import_string::to_node_import(file),
+        };
+
+        let aliases = self.aliases(source_file);
+        let used = aliases
+            .iter()
+            .position(|PathAlias { alias, .. }| alias.capture(import_string).is_some());
+        let others = aliases
+            .iter()
+            .enumerate()
+            .filter(|(idx, _)| Some(*idx) != used)
+            .map(|(_, alias)| alias);
+
+        used.map(|idx| &aliases[idx])
+            .into_iter()
+            .chain(others)
+            .find_map(|PathAlias { alias, targets }| {
+                targets
+                    .iter()
+                    .find_map(|target| target.capture_module(module_path, file))
+                    .map(|capture| alias.fill(capture))
+            })
+    }
+
+    pub fn is_import_from(
+        &self,
+        source_file: &Path,
+        required_file: &Path,
+        import_string: &str,
+    ) -> Result<bool> {
+        if import_string.starts_with('.') {
+            return import_string::is_import_from(source_file, required_file, import_string);
+        }
+
+        if import_string.starts_with('#') {
+            return Ok(
+                resolve_aliases(self.subpath_aliases(source_file), import_string)
+                    .iter()
+                    .any(|module_path| points_to(module_path, required_file)),
+            );
+        }
+
+        Ok(self
+            .resolve(source_file, import_string)
+            .iter()
+            .any(|module_path| points_to(module_path, required_file)))
+    }
+
+    pub fn rename_required_file(&self, args: &import_string::RequiredFileRename) -> Result<String> {
+        let import_string::RequiredFileRename {
+            source_file,
+            import_string,
+            old_location,
+            new_location,
+        } = args;
+
+        if import_string.starts_with('.') {
+            let new_import_string = import_string::rename_required_file(args)?;
+            return match new_import_string.eq(import_string) {
+                true => Ok(new_import_string),
+                false => self.to_package_import(source_file, new_import_string),
+            };
+        }
+
+        if import_string.starts_with('#') {
+            return self.rename_subpath_import(args, source_file);
+        }
+
+        if !self.is_import_from(source_file, old_location, import_string)? {
+            return Ok(import_string.to_string());
+        }
+
+        // Imports of emitted files keep naming the emitted file.
+        let is_emitted = self
+            .resolve(source_file, import_string)
+            .iter()
+            .any(|module_path| points_to_emitted(module_path, old_location));
+        let new_location = &match (is_emitted, import_string::to_emitted_file(new_location)) {
+            (true, Some(emitted_file)) => emitted_file,
+            _ => new_location.to_path_buf(),
+        };
+
+        // Files moved into the importer's own package are imported relatively.
+        match self.to_alias(source_file, import_string, new_location) {
+            Some(alias)
+                if !self.crosses_package(old_location, new_location)
+                    || !self.is_own_package_import(source_file, &alias) =>
+            {
+                Ok(alias)
+            }
+            _ => {
+                let has_suffix = PathBuf::from(import_string).extension().is_some();
+                let new_import_string = import_string::from_paths(source_file, new_location)?;
+
+                let new_import_string = match has_suffix {
+                    true => new_import_string,
+                    false => import_string::to_node_import(&new_import_string).to_string(),
+                };
+                self.to_package_import(source_file, new_import_string)
+            }
+        }
+    }
+    // Rewrites a subpath import of a moved file for an importer that moves to
+    // new_source_file. The import keeps using a pattern of the importer's
+    // package.json where one still matches the new location, and becomes a
+    // relative import otherwise.
+    pub fn rename_subpath_import(
+        &self,
+        args: &import_string::RequiredFileRename,
+        new_source_file: &Path,
+    ) -> Result<String> {
+        let import_string::RequiredFileRename {
+            source_file,
+            import_string,
+            old_location,
+            new_location,
+        } = args;
+
+        let module_paths = resolve_aliases(self.subpath_aliases(source_file), import_string);
+        if !module_paths
+            .iter()
+            .any(|module_path| points_to(module_path, old_location))
+        {
+            return Ok(import_string.to_string());
+        }
+
+        if old_location.eq(new_location) && self.is_same_subpath_scope(source_file, new_source_file)
+        {
+            return Ok(import_string.to_string());
+        }
+
+        // Imports of emitted files keep naming the emitted file.
+        let is_emitted = module_paths
+            .iter()
+            .any(|module_path| points_to_emitted(module_path, old_location));
+        let names_file = is_emitted
+            || module_paths.iter().any(|module_path| {
+                path::normalize(module_path).ok().as_deref() == Some(*old_location)
+            });
+        let new_location = &match (is_emitted, import_string::to_emitted_file(new_location)) {
+            (true, Some(emitted_file)) => emitted_file,
+            _ => new_location.to_path_buf(),
+        };
+
+        let file = path::to_str(new_location)?;
+        let module_path = match names_file {
+            true => file,
+            false => import_string::to_node_import(file),
+        };
+        let alias = self.subpath_aliases(new_source_file).iter().find_map(
+            |PathAlias { alias, targets }| {
+                targets
+                    .iter()
+                    .find_map(|target| target.capture_module(module_path, file))
+                    .map(|capture| alias.fill(capture))
+            },
+        );
+        if let Some(alias) = alias {
+            return Ok(alias);
+        }
+
+        let new_import_string = import_string::from_paths(new_source_file, new_location)?;
+        let new_import_string = match names_file {
+            true => new_import_string,
+            false => import_string::to_node_import(&new_import_string).to_string(),
+        };
+        self.to_package_import(new_source_file, new_import_string)
+    }
+
+    // Subpath imports of a moved file that does not leave its package.json
+    // stay the same. Otherwise they are rewritten for the new package.
+    pub fn move_subpath_import(
+        &self,
+        source_file: &Path,
+        target_file: &Path,
+        import_string: String,
+    ) -> Result<String> {
+        if self.is_same_subpath_scope(source_file, target_file) {
+            return Ok(import_string);
+        }
+
+        let required_file = resolve_aliases(self.subpath_aliases(source_file), &import_string)
+            .iter()
+            .filter_map(|module_path| import_string::from_paths(source_file, module_path).ok())
+            .find_map(|rel_import| normalize::resolve(source_file, &rel_import));
+
+        match required_file {
+            Some(required_file) => {
+                let args = import_string::RequiredFileRename {
+                    source_file,
+                    import_string: &import_string,
+                    old_location: &required_file,
+                    new_location: &required_file,
+                };
+                self.rename_subpath_import(&args, target_file)
+            }
+            None => Ok(import_string),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use std::path::{Path, PathBuf};
+
+    use super::Resolver;
+    use crate::import_string::RequiredFileRename;
+    use crate::workspace::Package;
+
+    const TSCONFIG: &str = r#"{
+        // comments are allowed in tsconfig.json
+        "compilerOptions": {
+            "baseUrl": "./src",
+            "paths": {
+                "@app/*": ["app/*"],
+                "@components/*": ["app/components/*", "vendor/components/*"],
+                "@config": ["config/index.ts"],
+            },
+        },
+    }"#;
+
+    fn resolver() -> Resolver {
+        Resolver::parse(Path::new("/project"), TSCONFIG).unwrap()
+    }
+
+    macro_rules! is_import_from_tests {
+        ($($name:ident: $value:expr,)*) => {
+        $(
+            #[test]
+            fn $name() {
+                let (required_file, import_string, expected) = $value;
+                let source_file: PathBuf = "/project/src/app/main.ts".into();
+                let required_file: PathBuf = required_file.into();
+
+                let result = resolver()
+                    .is_import_from(&source_file, &required_file, import_string)
+                    .unwrap();
+                assert_eq!(result, expected);
+            }
+        )*
+        }
+    }
+
+    is_import_from_tests! {
+        alias_is_import_from_0: ("/project/src/app/components/Button.tsx", "@app/components/Button", true),
+        alias_is_import_from_1: ("/project/src/app/components/Button.tsx", "@components/Button", true),
+        alias_is_import_from_2: ("/project/src/vendor/components/Icon.tsx", "@components/Icon", true),
+        alias_is_import_from_3: ("/project/src/app/utils/index.ts", "@app/utils", true),
+        alias_is_import_from_4: ("/project/src/config/index.ts", "@config", true),
+        alias_is_import_from_5: ("/project/src/app/logo.svg", "@app/logo.svg", true),
+        alias_is_import_from_6: ("/project/src/app/components/Button.tsx", "@app/Button", false),
+        alias_is_import_from_7: ("/project/src/app/components/Button.tsx", "react", false),
+        alias_is_import_from_9: ("/project/src/app/components/Button.tsx", "app/components/Button", true),
+        alias_is_import_from_10: ("/project/src/app/react.ts", "react", false),
+        alias_is_import_from_8: ("/project/src/app/components/Button.tsx", "./components/Button", true),
+        alias_is_import_from_11: ("/project/src/app/components/Button.tsx", "@app/components/Button.js", true),
+    }
+
+    macro_rules! rename_required_file_tests {
+        ($($name:ident: $value:expr,)*) => {
+        $(
+            #[test]
+            fn $name() {
+                let (import_string, old_location, new_location, expected) = $value;
+                let source_file: PathBuf = "/project/src/app/main.ts".into();
+                let old_location: PathBuf = old_location.into();
+                let new_location: PathBuf = new_location.into();
+
+                let args = RequiredFileRename {
+                    source_file: &source_file,
+                    import_string,
+                    old_location: &old_location,
+                    new_location: &new_location,
+                };
+
+                let result = resolver().rename_required_file(&args).unwrap();
+                assert_eq!(result, expected);
+            }
+        )*
+        }
+    }
+
+    rename_required_file_tests! {
+        alias_rename_0: ("@app/components/Button", "/project/src/app/components/Button.tsx", "/project/src/app/ui/Button.tsx", "@app/ui/Button"),
+        alias_rename_1: ("@components/Button", "/project/src/app/components/Button.tsx", "/project/src/app/components/button/index.tsx", "@components/button"),
+        alias_rename_2: ("@components/Button", "/project/src/app/components/Button.tsx", "/project/src/app/ui/Button.tsx", "@app/ui/Button"),
+        alias_rename_3: ("@app/logo.svg", "/project/src/app/logo.svg", "/project/src/app/assets/logo.svg", "@app/assets/logo.svg"),
+        alias_rename_4: ("@app/components/Button", "/project/src/app/components/Button.tsx", "/project/lib/Button.tsx", "../../lib/Button"),
+        alias_rename_5: ("@app/other", "/project/src/app/components/Button.tsx", "/project/src/app/ui/Button.tsx", "@app/other"),
+        alias_rename_6: ("@config", "/project/src/config/index.ts", "/project/settings/index.ts", "../../settings"),
+        alias_rename_8: ("app/components/Button", "/project/src/app/components/Button.tsx", "/project/src/app/ui/Button.tsx", "app/ui/Button"),
+        alias_rename_9: ("app/components/Button", "/project/src/app/components/Button.tsx", "/project/lib/Button.tsx", "../../lib/Button"),
+        alias_rename_10: ("react", "/project/src/app/components/Button.tsx", "/project/src/app/ui/Button.tsx", "react"),
+        alias_rename_11: ("@config", "/project/src/config/index.ts", "/project/src/settings/index.ts", "settings"),
+        alias_rename_7: ("./components/Button", "/project/src/app/components/Button.tsx", "/project/src/app/ui/Button.tsx", "./ui/Button"),
+        alias_rename_12: ("@app/components/Button.js", "/project/src/app/components/Button.tsx", "/project/src/app/ui/Button.tsx", "@app/ui/Button.js"),
+        alias_rename_13: ("@app/components/Button.js", "/project/src/app/components/Button.tsx", "/project/lib/Button.tsx", "../../lib/Button.js"),
+    }
+
+    #[test]
+    fn it_rewrites_workspace_package_imports() -> Result<()> {
+        let mut resolver = resolver();
+        resolver.add_packages(&[Package {
+            name: "@acme/ui".into(),
+            dir: "/project/packages/ui".into(),
+        }])?;
+
+        let source_file: PathBuf = "/project/src/app/main.ts".into();
+        let old_location: PathBuf = "/project/packages/ui/src/Button.tsx".into();
+        let new_location: PathBuf = "/project/packages/ui/src/button/index.tsx".into();
+
+        assert_eq!(
+            resolver.aliases_to(&old_location),
+            vec!["@acme/ui/src/Button"]
+        );
+
+        let args = RequiredFileRename {
+            source_file: &source_file,
+            import_string: "@acme/ui/src/Button",
+            old_location: &old_location,
+            new_location: &new_location,
+        };
+        assert_eq!(resolver.rename_required_file(&args)?, "@acme/ui/src/button");
+        Ok(())
+    }
+}
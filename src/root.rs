@@ -0,0 +1,81 @@
+use std::path::{Path, PathBuf};
+
+use crate::path;
+use crate::workspace;
+
+// Markers in order of preference. Outside workspaces the nearest package.json
+// wins, so that nested projects only scan the package that owns the file.
+const MARKERS: [&str; 3] = ["package.json", "tsconfig.json", ".git"];
+
+fn find_marker(start: &Path, marker: &str) -> Option<PathBuf> {
+    let mut dir = start.to_path_buf();
+    loop {
+        if dir.join(marker).exists() {
+            return Some(dir);
+        }
+        if !dir.pop() {
+            return None;
+        }
+    }
+}
+
+// Folder of the enclosing git repository, if any.
+pub fn find_git_dir(start: &Path) -> Option<PathBuf> {
+    find_marker(start, ".git")
+        .map(|dir| dir.join(".git"))
+        .filter(|git_dir| git_dir.is_dir())
+}
+
+// Walks up from the moved file or folder to find the enclosing project root.
+// Inside a workspace the workspace root wins, so that packages importing the
+// moved file by package name get updated too.
+pub fn find(source_path: &Path) -> Option<PathBuf> {
+    let start = match source_path.is_dir() {
+        true => source_path.to_path_buf(),
+        false => path::get_parent(source_path),
+    };
+
+    workspace::find_root(&start).or_else(|| {
+        MARKERS
+            .iter()
+            .find_map(|marker| find_marker(&start, marker))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::test_dir::TempDir;
+
+    #[test]
+    fn it_finds_nearest_package_root() {
+        let tmp = TempDir::new("root");
+        let package = tmp.join("packages/web");
+        let src = package.join("src/components");
+        fs::create_dir_all(&src).unwrap();
+        fs::create_dir_all(tmp.join(".git")).unwrap();
+        fs::write(package.join("package.json"), "{}").unwrap();
+        fs::write(package.join("src/tsconfig.json"), "{}").unwrap();
+
+        let root = super::find(&src.join("Button.tsx"));
+        assert_eq!(root, Some(package.clone()));
+
+        fs::remove_file(package.join("package.json")).unwrap();
+        let root = super::find(&src.join("Button.tsx"));
+        assert_eq!(root, Some(package.join("src")));
+
+        fs::remove_file(package.join("src/tsconfig.json")).unwrap();
+        let root = super::find(&src);
+        assert_eq!(root, Some(tmp.clone()));
+
+        fs::write(
+            tmp.join("package.json"),
+            r#"{ "workspaces": ["packages/*"] }"#,
+        )
+        .unwrap();
+        fs::write(package.join("package.json"), "{}").unwrap();
+        let root = super::find(&src.join("Button.tsx"));
+        assert_eq!(root, Some(tmp.clone()));
+    }
+}
@@ -0,0 +1,162 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::journal::Journal;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Step {
+    Wrote {
+        path: PathBuf,
+        original: String,
+        contents: String,
+    },
+    Renamed {
+        from: PathBuf,
+        to: PathBuf,
+    },
+    CreatedDir {
+        path: PathBuf,
+    },
+}
+
+// Applies file system changes one at a time and journals them, so that
+// everything done so far can be reverted if a later step fails.
+#[derive(Default)]
+pub struct Transaction {
+    journal: Vec<Step>,
+}
+
+// Writes to a temporary file next to path and renames it into place, so that
+// path never contains partially written contents.
+fn write_atomic(path: &Path, contents: &str) -> Result<()> {
+    let file_name = path
+        .file_name()
+        .and_then(|file_name| file_name.to_str())
+        .ok_or_else(|| anyhow!("Invalid file name {:?}", path))?;
+    let tmp_path = path.with_file_name(format!(".{}.mvts-tmp", file_name));
+
+    fs::write(&tmp_path, contents).map_err(|_| anyhow!("Failed to write {:?}", tmp_path))?;
+
+    if let Ok(metadata) = fs::metadata(path) {
+        fs::set_permissions(&tmp_path, metadata.permissions())?;
+    }
+
+    fs::rename(&tmp_path, path).map_err(|_| {
+        let _ = fs::remove_file(&tmp_path);
+        anyhow!("Failed to write {:?}", path)
+    })
+}
+
+impl Transaction {
+    pub fn write(&mut self, path: &Path, original: String, contents: String) -> Result<()> {
+        write_atomic(path, &contents)?;
+        self.journal.push(Step::Wrote {
+            path: path.to_path_buf(),
+            original,
+            contents,
+        });
+        Ok(())
+    }
+
+    fn create_parent_dirs(&mut self, path: &Path) -> Result<()> {
+        let mut missing = vec![];
+        let mut dir = path.parent();
+
+        while let Some(parent) = dir.filter(|parent| !parent.exists()) {
+            missing.push(parent.to_path_buf());
+            dir = parent.parent();
+        }
+
+        for dir in missing.into_iter().rev() {
+            fs::create_dir(&dir).map_err(|_| anyhow!("Failed to create {:?}", dir))?;
+            self.journal.push(Step::CreatedDir { path: dir });
+        }
+
+        Ok(())
+    }
+
+    pub fn rename(&mut self, from: &Path, to: &Path) -> Result<()> {
+        if !from.exists() {
+            return Err(anyhow!("{:?} does not exist", from));
+        }
+        if to.exists() {
+            return Err(anyhow!("{:?} already exists", to));
+        }
+
+        self.create_parent_dirs(to)?;
+        fs::rename(from, to).map_err(|_| anyhow!("Failed to rename {:?} to {:?}", from, to))?;
+        self.journal.push(Step::Renamed {
+            from: from.to_path_buf(),
+            to: to.to_path_buf(),
+        });
+        Ok(())
+    }
+
+    pub fn into_journal(self) -> Journal {
+        Journal {
+            steps: self.journal,
+        }
+    }
+
+    // Reverts every journaled step in reverse order. Keeps going after a
+    // failure so that as much as possible gets restored.
+    pub fn rollback(self) -> Result<()> {
+        let errors: Vec<String> = self
+            .journal
+            .into_iter()
+            .rev()
+            .filter_map(|step| {
+                let result = match &step {
+                    Step::Wrote { path, original, .. } => write_atomic(path, original),
+                    Step::Renamed { from, to } => fs::rename(to, from)
+                        .map_err(|_| anyhow!("Failed to rename {:?} back to {:?}", to, from)),
+                    Step::CreatedDir { path } => {
+                        fs::remove_dir(path).map_err(|_| anyhow!("Failed to remove {:?}", path))
+                    }
+                };
+                result.err().map(|err| err.to_string())
+            })
+            .collect();
+
+        match errors.is_empty() {
+            true => Ok(()),
+            false => Err(anyhow!("Rollback failed:\n{}", errors.join("\n"))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use std::fs;
+
+    use crate::test_dir::TempDir;
+
+    #[test]
+    fn it_rolls_back_writes_and_renames() -> Result<()> {
+        let tmp = TempDir::new("transaction");
+        fs::write(tmp.join("a.ts"), "a")?;
+        fs::write(tmp.join("b.ts"), "b")?;
+
+        let mut transaction = super::Transaction::default();
+        transaction.write(&tmp.join("a.ts"), "a".into(), "changed".into())?;
+        transaction.rename(&tmp.join("b.ts"), &tmp.join("c/d/b.ts"))?;
+        assert!(transaction
+            .rename(&tmp.join("a.ts"), &tmp.join("c/d/b.ts"))
+            .is_err());
+
+        assert_eq!(fs::read_to_string(tmp.join("a.ts"))?, "changed");
+        assert!(tmp.join("c/d/b.ts").exists());
+
+        transaction.rollback()?;
+
+        assert_eq!(fs::read_to_string(tmp.join("a.ts"))?, "a");
+        assert_eq!(fs::read_to_string(tmp.join("b.ts"))?, "b");
+        assert!(!tmp.join("c").exists());
+
+        Ok(())
+    }
+}
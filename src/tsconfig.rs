@@ -0,0 +1,406 @@
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::edit::TextEdit;
+use crate::grep;
+use crate::jsonc;
+use crate::path;
+use crate::plan::{FileEdit, MovePlan};
+use crate::resolver::{PathAlias, Pattern};
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct CompilerOptions {
+    base_url: Option<String>,
+    paths: Option<BTreeMap<String, Vec<String>>>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Extends {
+    One(String),
+    Many(Vec<String>),
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct RawTsConfig {
+    #[serde(default)]
+    compiler_options: CompilerOptions,
+    extends: Option<Extends>,
+}
+
+// Compiler options after following `extends`, with baseUrl made absolute.
+// Without baseUrl, paths are relative to the config that declares them.
+#[derive(Default)]
+struct ResolvedOptions {
+    base_url: Option<PathBuf>,
+    paths: Option<(BTreeMap<String, Vec<String>>, PathBuf)>,
+}
+
+// Path aliases of the tsconfig.json in dir, which apply to files below dir.
+// The baseUrl catch-all is kept apart, as workspace packages are tried
+// before it.
+pub struct ConfigScope {
+    pub dir: PathBuf,
+    pub aliases: Vec<PathAlias>,
+    pub catch_all: Option<PathAlias>,
+}
+
+// Reads the compiler options of a config and the configs it extends, where
+// later configs override earlier ones and the config itself overrides all.
+fn read_options(config_file: &Path, seen: &mut Vec<PathBuf>) -> Result<ResolvedOptions> {
+    let contents =
+        fs::read_to_string(config_file).map_err(|_| anyhow!("Failed to read {:?}", config_file))?;
+    let RawTsConfig {
+        compiler_options,
+        extends,
+    } = jsonc::parse(&contents)
+        .map_err(|err| anyhow!("Failed to parse {:?}: {}", config_file, err))?;
+    let dir = path::get_parent(config_file);
+    seen.push(config_file.to_path_buf());
+
+    let extends = match extends {
+        Some(Extends::One(extends)) => vec![extends],
+        Some(Extends::Many(extends)) => extends,
+        None => vec![],
+    };
+
+    let mut options = ResolvedOptions::default();
+    for extends in extends {
+        let extended_file = match to_extended_file(&dir, &extends) {
+            Some(file) if !seen.contains(&file) => file,
+            _ => continue,
+        };
+        let extended = read_options(&extended_file, seen)?;
+        if extended.base_url.is_some() {
+            options.base_url = extended.base_url;
+        }
+        if extended.paths.is_some() {
+            options.paths = extended.paths;
+        }
+    }
+
+    let own_options = to_options(&dir, compiler_options)?;
+    if own_options.base_url.is_some() {
+        options.base_url = own_options.base_url;
+    }
+    if own_options.paths.is_some() {
+        options.paths = own_options.paths;
+    }
+
+    Ok(options)
+}
+
+fn to_options(config_dir: &Path, compiler_options: CompilerOptions) -> Result<ResolvedOptions> {
+    let base_url = match &compiler_options.base_url {
+        Some(base_url) => Some(path::join(config_dir, Path::new(base_url))?),
+        None => None,
+    };
+    let paths = compiler_options
+        .paths
+        .map(|paths| (paths, config_dir.to_path_buf()));
+
+    Ok(ResolvedOptions { base_url, paths })
+}
+
+fn to_scope(config_dir: &Path, options: ResolvedOptions) -> Result<ConfigScope> {
+    let ResolvedOptions { base_url, paths } = options;
+    let (paths, paths_dir) = paths.unwrap_or_default();
+    let base_dir = base_url.clone().unwrap_or(paths_dir);
+
+    let aliases = paths
+        .into_iter()
+        .map(|(alias, targets)| -> Result<PathAlias> {
+            let targets = targets
+                .iter()
+                .map(|target| -> Result<Pattern> {
+                    let target = path::join(&base_dir, Path::new(target))?;
+                    Ok(Pattern::new(path::to_str(&target)?))
+                })
+                .collect::<Result<Vec<Pattern>>>()?;
+
+            Ok(PathAlias {
+                alias: Pattern::new(&alias),
+                targets,
+            })
+        })
+        .collect::<Result<Vec<PathAlias>>>()?;
+
+    // Bare imports such as "components/Button" resolve against baseUrl
+    // after paths have been tried, which works like a catch-all alias.
+    let catch_all = match base_url {
+        Some(base_url) => {
+            let target = path::join(&base_url, Path::new("*"))?;
+            Some(PathAlias {
+                alias: Pattern::new("*"),
+                targets: vec![Pattern::new(path::to_str(&target)?)],
+            })
+        }
+        None => None,
+    };
+
+    Ok(ConfigScope {
+        dir: config_dir.to_path_buf(),
+        aliases,
+        catch_all,
+    })
+}
+
+// Every tsconfig.json under root is read, as packages of a workspace often
+// have their own. Each applies to the files in its folder.
+pub fn read_config_scopes(root: &Path) -> Result<Vec<ConfigScope>> {
+    grep::iter_all_files(root)
+        .filter(|file| file.file_name() == Some("tsconfig.json".as_ref()))
+        .filter(|file| {
+            !file
+                .strip_prefix(root)
+                .map(grep::is_in_node_modules)
+                .unwrap_or(false)
+        })
+        .map(|config_file| {
+            let options = read_options(&config_file, &mut vec![])?;
+            to_scope(&path::get_parent(&config_file), options)
+        })
+        .collect()
+}
+
+// Parses a single config, without following `extends`.
+#[cfg(test)]
+pub fn parse_config_scope(config_dir: &Path, contents: &str) -> Result<ConfigScope> {
+    let RawTsConfig {
+        compiler_options, ..
+    } = jsonc::parse(contents)?;
+    to_scope(config_dir, to_options(config_dir, compiler_options)?)
+}
+
+fn is_config_file(file: &Path) -> bool {
+    let file_name = file
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default();
+    (file_name.starts_with("tsconfig") || file_name.starts_with("jsconfig"))
+        && file_name.ends_with(".json")
+}
+
+// Config file named by a relative `extends`, which may leave out `.json`.
+fn to_extended_file(config_dir: &Path, extends: &str) -> Option<PathBuf> {
+    if !extends.starts_with('.') {
+        return None;
+    }
+    let file = path::join(config_dir, Path::new(extends)).ok()?;
+    match file.is_file() {
+        true => Some(file),
+        false => Some(PathBuf::from(format!("{}.json", path::to_str(&file).ok()?)))
+            .filter(|file| file.is_file()),
+    }
+}
+
+// Config fields holding paths relative to the config folder.
+fn is_path_field(keys: &[&str]) -> bool {
+    matches!(
+        keys,
+        ["files", _]
+            | ["include", _]
+            | ["exclude", _]
+            | ["references", _, "path"]
+            | ["compilerOptions", "baseUrl"]
+    )
+}
+
+// Plans rewriting the paths in every tsconfig.json under root, and in the
+// configs they extend, that point to files or folders moved by the plan. Only
+// the changed strings are replaced, so comments and formatting are kept.
+pub fn plan_tsconfig_files(root: &Path, plan: &MovePlan) -> Result<Vec<FileEdit>> {
+    let mut config_files: Vec<PathBuf> = grep::iter_all_files(root)
+        .filter(|file| is_config_file(file) && !grep::is_in_node_modules(file))
+        .collect();
+    let mut file_edits = vec![];
+    let mut idx = 0;
+
+    while idx < config_files.len() {
+        let file = config_files[idx].clone();
+        idx += 1;
+
+        let contents =
+            fs::read_to_string(&file).map_err(|_| anyhow!("Failed to read {:?}", file))?;
+        let strings = match jsonc::strings(&contents) {
+            Ok(strings) => strings,
+            Err(_) => continue,
+        };
+        let dir = path::get_parent(&file);
+        let new_dir = plan.new_location(&dir);
+
+        // Targets of `paths` are relative to baseUrl when it is set.
+        let base_dir = strings
+            .iter()
+            .find(|string| string.keys.eq(&["compilerOptions", "baseUrl"]))
+            .and_then(|string| path::join(&dir, Path::new(&string.value)).ok())
+            .unwrap_or_else(|| dir.clone());
+        let new_base_dir = plan.new_location(&base_dir);
+
+        let mut edits = vec![];
+        for string in strings {
+            let keys: Vec<&str> = string.keys.iter().map(|key| key.as_str()).collect();
+
+            let new_text = match keys.as_slice() {
+                ["extends"] | ["extends", _] => {
+                    if let Some(extended_file) = to_extended_file(&dir, &string.value) {
+                        if !config_files.contains(&extended_file)
+                            && !grep::is_in_node_modules(&extended_file)
+                        {
+                            config_files.push(extended_file);
+                        }
+                        match string.value.ends_with(".json") {
+                            true => plan.rewrite_path(&dir, &new_dir, &string.value),
+                            false => plan
+                                .rewrite_path(&dir, &new_dir, &format!("{}.json", string.value))
+                                .map(|new_text| new_text.trim_end_matches(".json").to_owned()),
+                        }
+                    } else {
+                        None
+                    }
+                }
+                ["compilerOptions", "paths", _, _] => {
+                    plan.rewrite_path(&base_dir, &new_base_dir, &string.value)
+                }
+                keys if is_path_field(keys) => plan.rewrite_path(&dir, &new_dir, &string.value),
+                _ => None,
+            };
+
+            if let Some(new_text) = new_text {
+                edits.push(TextEdit {
+                    start: string.start,
+                    end: string.end,
+                    old_text: contents[string.start..string.end].to_owned(),
+                    new_text,
+                });
+            }
+        }
+
+        if !edits.is_empty() {
+            file_edits.push(FileEdit { path: file, edits });
+        }
+    }
+
+    Ok(file_edits)
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use std::fs;
+
+    use crate::plan::{self, Rename};
+    use crate::resolver::Resolver;
+    use crate::test_dir::TempDir;
+
+    #[test]
+    fn it_rewrites_package_tsconfig_aliases() -> Result<()> {
+        let tmp = TempDir::new("package-tsconfig");
+        let web = tmp.join("packages/web");
+        let admin = tmp.join("packages/admin");
+        fs::create_dir_all(web.join("src/components"))?;
+        fs::create_dir_all(web.join("src/pages"))?;
+        fs::create_dir_all(admin.join("src"))?;
+        fs::create_dir_all(tmp.join("other/components"))?;
+        fs::write(
+            tmp.join("package.json"),
+            r#"{ "workspaces": ["packages/*"] }"#,
+        )?;
+        fs::write(
+            tmp.join("tsconfig.json"),
+            r#"{ "compilerOptions": { "paths": { "@app/*": ["other/*"] } } }"#,
+        )?;
+        fs::write(
+            web.join("tsconfig.json"),
+            r#"{ "compilerOptions": { "paths": { "@app/*": ["src/*"] } } }"#,
+        )?;
+        fs::write(
+            admin.join("tsconfig.json"),
+            r#"{ "extends": "../web/tsconfig" }"#,
+        )?;
+        fs::write(web.join("src/components/Button.tsx"), "")?;
+        fs::write(tmp.join("other/components/Button.tsx"), "")?;
+        let code = "import '@app/components/Button';\n";
+        fs::write(web.join("src/pages/home.ts"), code)?;
+        fs::write(admin.join("src/main.ts"), code)?;
+        fs::write(tmp.join("other/main.ts"), code)?;
+
+        let resolver = Resolver::load(&tmp)?;
+        let renames = vec![Rename {
+            from: web.join("src/components/Button.tsx"),
+            to: web.join("src/ui/Button.tsx"),
+        }];
+        plan::plan_moves(&tmp, renames, &resolver)?.apply()?;
+
+        let moved = "import '@app/ui/Button';\n";
+        assert_eq!(fs::read_to_string(web.join("src/pages/home.ts"))?, moved);
+        assert_eq!(fs::read_to_string(admin.join("src/main.ts"))?, moved);
+        assert_eq!(fs::read_to_string(tmp.join("other/main.ts"))?, code);
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_updates_tsconfig_paths() -> Result<()> {
+        let tmp = TempDir::new("tsconfig-files");
+        fs::create_dir_all(tmp.join("app/src/legacy"))?;
+        fs::create_dir_all(tmp.join("core/src"))?;
+        fs::create_dir_all(tmp.join("config"))?;
+        fs::write(tmp.join("app/src/legacy/shim.ts"), "")?;
+        fs::write(tmp.join("core/src/index.ts"), "")?;
+        fs::write(tmp.join("core/tsconfig.json"), "{}")?;
+        fs::write(
+            tmp.join("config/base.json"),
+            r#"{ "compilerOptions": { "paths": { "@core/*": ["../core/src/*"] } } }"#,
+        )?;
+        fs::write(
+            tmp.join("app/tsconfig.json"),
+            r#"{
+  // Shared options
+  "extends": "../config/base",
+  "compilerOptions": { "baseUrl": "src" },
+  "files": ["src/legacy/shim.ts"],
+  "include": ["src/legacy/**/*", "src/**/*.d.ts"],
+  "references": [{ "path": "../core" }],
+}
+"#,
+        )?;
+
+        let renames = vec![
+            Rename {
+                from: tmp.join("app/src/legacy"),
+                to: tmp.join("app/src/compat"),
+            },
+            Rename {
+                from: tmp.join("core"),
+                to: tmp.join("packages/core"),
+            },
+        ];
+        fs::create_dir_all(tmp.join("packages"))?;
+        plan::plan_moves(&tmp.join("app"), renames, &Resolver::default())?.apply()?;
+
+        assert_eq!(
+            fs::read_to_string(tmp.join("app/tsconfig.json"))?,
+            r#"{
+  // Shared options
+  "extends": "../config/base",
+  "compilerOptions": { "baseUrl": "src" },
+  "files": ["src/compat/shim.ts"],
+  "include": ["src/compat/**/*", "src/**/*.d.ts"],
+  "references": [{ "path": "../packages/core" }],
+}
+"#
+        );
+        assert_eq!(
+            fs::read_to_string(tmp.join("config/base.json"))?,
+            r#"{ "compilerOptions": { "paths": { "@core/*": ["../packages/core/src/*"] } } }"#
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,400 @@
+use anyhow::{anyhow, Result};
+use globset::{GlobBuilder, GlobSetBuilder};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::grep;
+use crate::jsonc;
+use crate::plan::{FileEdit, MovePlan};
+
+const PACKAGE_JSON: &str = "package.json";
+const PNPM_WORKSPACE: &str = "pnpm-workspace.yaml";
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Workspaces {
+    Globs(Vec<String>),
+    Object {
+        #[serde(default)]
+        packages: Vec<String>,
+    },
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct PackageJson {
+    name: Option<String>,
+    workspaces: Option<Workspaces>,
+    #[serde(default)]
+    dependencies: BTreeMap<String, Value>,
+    #[serde(default)]
+    dev_dependencies: BTreeMap<String, Value>,
+    #[serde(default)]
+    peer_dependencies: BTreeMap<String, Value>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Package {
+    pub name: String,
+    pub dir: PathBuf,
+}
+
+// Packages of a yarn, npm or pnpm workspace.
+#[derive(Debug, Default)]
+pub struct Workspace {
+    pub packages: Vec<Package>,
+}
+
+// A package import added by a move to a package that does not yet list the
+// imported package as a dependency.
+#[derive(Debug, PartialEq)]
+pub struct NewDependency {
+    pub file: PathBuf,
+    pub package: String,
+    pub dependency: String,
+}
+
+// Innermost package containing file.
+pub fn package_of<'a>(packages: &'a [Package], file: &Path) -> Option<&'a Package> {
+    packages
+        .iter()
+        .filter(|package| file.starts_with(&package.dir))
+        .max_by_key(|package| package.dir.components().count())
+}
+
+pub fn is_import_of(import_string: &str, Package { name, .. }: &Package) -> bool {
+    import_string.eq(name) || import_string.starts_with(&format!("{}/", name))
+}
+
+fn read_package_json(dir: &Path) -> Option<PackageJson> {
+    let contents = fs::read_to_string(dir.join(PACKAGE_JSON)).ok()?;
+    jsonc::parse(&contents).ok()
+}
+
+// Reads the package globs from pnpm-workspace.yaml, which only needs a list
+// of strings under `packages:`, so no YAML parser is pulled in for it.
+fn read_pnpm_globs(dir: &Path) -> Option<Vec<String>> {
+    let contents = fs::read_to_string(dir.join(PNPM_WORKSPACE)).ok()?;
+    let mut globs = vec![];
+    let mut in_packages = false;
+
+    for line in contents.lines() {
+        let line = line.split(" #").next().unwrap_or_default().trim_end();
+        if line.trim().is_empty() || line.trim_start().starts_with('#') {
+            continue;
+        }
+        if !line.starts_with(' ') && !line.starts_with('-') {
+            in_packages = line.trim().eq("packages:");
+            continue;
+        }
+        if let Some(glob) = line.trim().strip_prefix('-').filter(|_| in_packages) {
+            globs.push(
+                glob.trim()
+                    .trim_matches(|c| c == '"' || c == '\'')
+                    .to_owned(),
+            );
+        }
+    }
+
+    Some(globs)
+}
+
+fn read_globs(dir: &Path) -> Option<Vec<String>> {
+    let workspaces = read_package_json(dir).and_then(|package_json| package_json.workspaces);
+
+    match workspaces {
+        Some(Workspaces::Globs(globs)) => Some(globs),
+        Some(Workspaces::Object { packages }) => Some(packages),
+        None => read_pnpm_globs(dir),
+    }
+}
+
+// Every package.json under root outside of node_modules.
+pub fn iter_package_json_files(root: &Path) -> impl Iterator<Item = PathBuf> {
+    grep::iter_all_files(root)
+        .filter(|file| file.ends_with(PACKAGE_JSON))
+        .filter(|file| !grep::is_in_node_modules(file))
+}
+
+// Walks up from start to the folder that declares the workspace.
+pub fn find_root(start: &Path) -> Option<PathBuf> {
+    let mut dir = start.to_path_buf();
+    loop {
+        if read_globs(&dir).is_some() {
+            return Some(dir);
+        }
+        if !dir.pop() {
+            return None;
+        }
+    }
+}
+
+impl Workspace {
+    // Finds the named packages matching the workspace globs under root. An
+    // empty workspace is returned when root does not declare one.
+    pub fn load(root: &Path) -> Result<Self> {
+        let globs = match read_globs(root) {
+            Some(globs) => globs,
+            None => return Ok(Self::default()),
+        };
+
+        let mut includes = GlobSetBuilder::new();
+        let mut excludes = GlobSetBuilder::new();
+        for glob in &globs {
+            let (set, glob) = match glob.strip_prefix('!') {
+                Some(glob) => (&mut excludes, glob),
+                None => (&mut includes, glob.as_str()),
+            };
+            let glob = glob.trim_start_matches("./").trim_end_matches('/');
+            set.add(
+                GlobBuilder::new(glob)
+                    .literal_separator(true)
+                    .build()
+                    .map_err(|err| anyhow!("Invalid workspace glob {:?}: {}", glob, err))?,
+            );
+        }
+        let includes = includes.build()?;
+        let excludes = excludes.build()?;
+
+        let mut packages: Vec<Package> = iter_package_json_files(root)
+            .filter_map(|file| {
+                let dir = file.parent()?.to_path_buf();
+                let rel_dir = dir.strip_prefix(root).ok()?;
+                match includes.is_match(rel_dir) && !excludes.is_match(rel_dir) {
+                    true => Some(dir),
+                    false => None,
+                }
+            })
+            .filter_map(|dir| {
+                let name = read_package_json(&dir)?.name?;
+                Some(Package { name, dir })
+            })
+            .collect();
+
+        packages.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(Self { packages })
+    }
+
+    // Finds imports that a plan turns into imports of another package which
+    // the importing package does not depend on.
+    pub fn new_dependencies(&self, plan: &MovePlan) -> Vec<NewDependency> {
+        let mut new_dependencies = vec![];
+
+        for FileEdit { path, edits } in &plan.edits {
+            let file = plan.new_location(path);
+            let package = match package_of(&self.packages, &file) {
+                Some(package) => package,
+                None => continue,
+            };
+            let package_json = read_package_json(&package.dir).unwrap_or_default();
+
+            for edit in edits {
+                let dependency = self.packages.iter().find(|dependency| {
+                    is_import_of(&edit.new_text, dependency)
+                        && !is_import_of(&edit.old_text, dependency)
+                        && dependency.ne(&package)
+                });
+                let dependency = match dependency {
+                    Some(dependency) => dependency.name.clone(),
+                    None => continue,
+                };
+
+                let is_listed = [
+                    &package_json.dependencies,
+                    &package_json.dev_dependencies,
+                    &package_json.peer_dependencies,
+                ]
+                .iter()
+                .any(|dependencies| dependencies.contains_key(&dependency));
+
+                let new_dependency = NewDependency {
+                    file: file.clone(),
+                    package: package.name.clone(),
+                    dependency,
+                };
+                if !is_listed && !new_dependencies.contains(&new_dependency) {
+                    new_dependencies.push(new_dependency);
+                }
+            }
+        }
+
+        new_dependencies
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    use super::{Package, Workspace};
+    use crate::plan::{self, Rename};
+    use crate::resolver::Resolver;
+    use crate::test_dir::TempDir;
+
+    fn write_package(dir: &Path, name: &str) -> Result<()> {
+        fs::create_dir_all(dir)?;
+        fs::write(
+            dir.join("package.json"),
+            format!(r#"{{ "name": "{}" }}"#, name),
+        )?;
+        Ok(())
+    }
+
+    #[test]
+    fn it_loads_workspace_packages() -> Result<()> {
+        let tmp = TempDir::new("workspace");
+        write_package(&tmp.join("packages/ui"), "@acme/ui")?;
+        write_package(&tmp.join("packages/app"), "@acme/app")?;
+        write_package(&tmp.join("packages/legacy"), "@acme/legacy")?;
+        write_package(&tmp.join("packages/app/node_modules/dep"), "dep")?;
+        write_package(&tmp.join("tools/cli"), "cli")?;
+        fs::write(
+            tmp.join("package.json"),
+            r#"{ "name": "acme", "workspaces": ["packages/*", "!packages/legacy"] }"#,
+        )?;
+
+        assert_eq!(
+            super::find_root(&tmp.join("packages/ui/src")),
+            Some(tmp.clone())
+        );
+        let workspace = Workspace::load(&tmp)?;
+        assert_eq!(
+            workspace.packages,
+            vec![
+                Package {
+                    name: "@acme/app".into(),
+                    dir: tmp.join("packages/app")
+                },
+                Package {
+                    name: "@acme/ui".into(),
+                    dir: tmp.join("packages/ui")
+                },
+            ]
+        );
+
+        fs::write(tmp.join("package.json"), r#"{ "name": "acme" }"#)?;
+        fs::write(
+            tmp.join("pnpm-workspace.yaml"),
+            "packages:\n  # all tools\n  - 'tools/*'\n  - \"packages/ui\"\n",
+        )?;
+        let workspace = Workspace::load(&tmp)?;
+        let names: Vec<String> = workspace.packages.into_iter().map(|p| p.name).collect();
+        assert_eq!(names, vec!["@acme/ui", "cli"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_moves_files_between_packages() -> Result<()> {
+        let tmp = TempDir::new("workspace-move");
+        write_package(&tmp.join("packages/app"), "@acme/app")?;
+        write_package(&tmp.join("packages/shared"), "@acme/shared")?;
+        fs::create_dir_all(tmp.join("packages/app/src"))?;
+        fs::create_dir_all(tmp.join("packages/shared/src"))?;
+        fs::write(
+            tmp.join("package.json"),
+            r#"{ "workspaces": ["packages/*"] }"#,
+        )?;
+        fs::write(
+            tmp.join("packages/app/src/format.ts"),
+            "import { helper } from './helper';\nimport { date } from '@acme/shared/src/date';\n",
+        )?;
+        fs::write(tmp.join("packages/app/src/helper.ts"), "")?;
+        fs::write(
+            tmp.join("packages/app/src/main.ts"),
+            "import { format } from './format';\n",
+        )?;
+        fs::write(tmp.join("packages/shared/src/date.ts"), "")?;
+
+        let workspace = Workspace::load(&tmp)?;
+        let mut resolver = Resolver::default();
+        resolver.add_packages(&workspace.packages)?;
+
+        let renames = vec![Rename {
+            from: tmp.join("packages/app/src/format.ts"),
+            to: tmp.join("packages/shared/src/format.ts"),
+        }];
+        let plan = plan::plan_moves(&tmp, renames, &resolver)?;
+
+        let mut new_dependencies: Vec<(PathBuf, String, String)> = workspace
+            .new_dependencies(&plan)
+            .into_iter()
+            .map(|dep| (dep.file, dep.package, dep.dependency))
+            .collect();
+        new_dependencies.sort();
+        assert_eq!(
+            new_dependencies,
+            vec![
+                (
+                    tmp.join("packages/app/src/main.ts"),
+                    "@acme/app".into(),
+                    "@acme/shared".into()
+                ),
+                (
+                    tmp.join("packages/shared/src/format.ts"),
+                    "@acme/shared".into(),
+                    "@acme/app".into()
+                ),
+            ]
+        );
+
+        plan.apply()?;
+        assert_eq!(
+            fs::read_to_string(tmp.join("packages/shared/src/format.ts"))?,
+            "import { helper } from '@acme/app/src/helper';\nimport { date } from './date';\n"
+        );
+        assert_eq!(
+            fs::read_to_string(tmp.join("packages/app/src/main.ts"))?,
+            "import { format } from '@acme/shared/src/format';\n"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_keeps_package_imports_within_a_package() -> Result<()> {
+        let tmp = TempDir::new("workspace-move-within");
+        write_package(&tmp.join("packages/ui"), "@acme/ui")?;
+        fs::create_dir_all(tmp.join("packages/ui/src/a"))?;
+        fs::write(
+            tmp.join("package.json"),
+            r#"{ "workspaces": ["packages/*"] }"#,
+        )?;
+        fs::write(
+            tmp.join("packages/ui/src/a/file.ts"),
+            "import { x } from '@acme/ui/src/x';\n",
+        )?;
+        fs::write(tmp.join("packages/ui/src/x.ts"), "")?;
+        fs::write(
+            tmp.join("packages/ui/src/c.ts"),
+            "import { file } from '@acme/ui/src/a/file';\n",
+        )?;
+
+        let workspace = Workspace::load(&tmp)?;
+        let mut resolver = Resolver::default();
+        resolver.add_packages(&workspace.packages)?;
+
+        let renames = vec![Rename {
+            from: tmp.join("packages/ui/src/a/file.ts"),
+            to: tmp.join("packages/ui/src/b/file.ts"),
+        }];
+        plan::plan_moves(&tmp, renames, &resolver)?.apply()?;
+
+        assert_eq!(
+            fs::read_to_string(tmp.join("packages/ui/src/b/file.ts"))?,
+            "import { x } from '@acme/ui/src/x';\n"
+        );
+        assert_eq!(
+            fs::read_to_string(tmp.join("packages/ui/src/c.ts"))?,
+            "import { file } from '@acme/ui/src/b/file';\n"
+        );
+
+        Ok(())
+    }
+}